@@ -0,0 +1,57 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use day3::{count_trees, BitForest, Horizontal, CELL_WIDTH};
+
+const ROWS: usize = 1_000_000;
+const SLOPES: [(usize, usize); 5] = [(1, 1), (3, 1), (5, 1), (7, 1), (1, 2)];
+
+/// A large forest of `CELL_WIDTH` wide rows, with roughly one tree in four cells.
+fn generate_rows() -> Vec<String> {
+    let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+    (0..ROWS)
+        .map(|_| {
+            (0..CELL_WIDTH)
+                .map(|_| {
+                    seed ^= seed << 13;
+                    seed ^= seed >> 7;
+                    seed ^= seed << 17;
+                    if seed.is_multiple_of(4) { '#' } else { '.' }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn slopes(c: &mut Criterion) {
+    let rows = generate_rows();
+    let hill: Vec<Horizontal> = rows.iter().cloned().map(Horizontal::new).collect();
+    let mut forest = BitForest::new();
+    for row in rows.iter() {
+        forest.push_row(row).unwrap();
+    }
+
+    let mut group = c.benchmark_group("slopes");
+    group.bench_function("cells", |b| {
+        b.iter(|| {
+            SLOPES
+                .iter()
+                .map(|(right, down)| count_trees(black_box(&hill), *right, *down))
+                .product::<u64>()
+        })
+    });
+    group.bench_function("bits_per_slope", |b| {
+        b.iter(|| {
+            SLOPES
+                .iter()
+                .map(|(right, down)| black_box(&forest).count_trees(*right, *down).unwrap())
+                .product::<u64>()
+        })
+    });
+    group.bench_function("bits_single_pass", |b| {
+        b.iter(|| black_box(&forest).count_slopes(&SLOPES).unwrap().iter().product::<u64>())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, slopes);
+criterion_main!(benches);
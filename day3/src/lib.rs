@@ -0,0 +1,292 @@
+use std::ops::Index;
+
+mod route;
+
+pub use route::{parse_move, Route};
+
+pub const CELL_WIDTH: usize = 31;
+
+const WORD_BITS: usize = 64;
+
+pub struct Horizontal {
+    row: [Cell; CELL_WIDTH]
+}
+
+#[derive(Copy, Clone, std::cmp::PartialEq)]
+pub enum Cell {
+    Empty,
+    Tree
+}
+
+impl Horizontal {
+    pub fn new(line: String) -> Horizontal {
+        let mut horizontal = Horizontal { row: [Cell::Empty; CELL_WIDTH] };
+        let line_iter = line.chars();
+        for (i, a_char) in line_iter.enumerate() {
+            horizontal.row[i] = match a_char {
+                '.' => Cell::Empty,
+                '#' => Cell::Tree,
+                _ => panic!("Unexpected Character in input.")
+            };
+        }
+
+        horizontal
+    }
+}
+
+impl Index<usize> for Horizontal {
+    type Output = Cell;
+
+    fn index(&self, i: usize) ->  &Self::Output {
+        &self.row[i % CELL_WIDTH]
+    }
+}
+
+/// Walks the hill one row at a time for a single slope, counting the trees hit.
+pub fn count_trees(hill: &[Horizontal], right: usize, down: usize) -> u64 {
+    let mut hits = 0;
+    let mut horiz_posn = 0;
+    for row_idx in (down..hill.len()).step_by(down) {
+        horiz_posn += right;
+        if hill[row_idx][horiz_posn] == Cell::Tree {
+            hits += 1;
+        }
+    }
+    hits
+}
+
+/// Why a row could not be added to a `BitForest`.
+#[derive(Debug, PartialEq)]
+pub enum RowError {
+    Width { expected: usize, found: usize },
+    /// `column` counts from 1.
+    Character { column: usize, found: char }
+}
+
+impl std::fmt::Display for RowError {
+    fn fmt (&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RowError::Width { expected, found } => write!(f, "is {} wide, expected {}", found, expected),
+            RowError::Character { column, found } => write!(f, "has '{}' at column {}", found, column)
+        }
+    }
+}
+
+impl std::error::Error for RowError {}
+
+/// A forest with every row packed into 64 bit words, one bit per cell (set for a tree).
+/// Rows can be any width, and repeat to the right like the `Horizontal` rows do.
+pub struct BitForest {
+    width: usize,
+    words_per_row: usize,
+    height: usize,
+    bits: Vec<u64>
+}
+
+impl BitForest {
+    pub fn new() -> BitForest {
+        BitForest { width: 0, words_per_row: 0, height: 0, bits: vec![] }
+    }
+
+    /// Appends a row of `.` and `#`. The first row sets the width of the forest.
+    pub fn push_row(&mut self, line: &str) -> Result<(), RowError> {
+        if let Some((i, found)) = line.chars().enumerate().find(|(_, c)| *c != '.' && *c != '#') {
+            return Err(RowError::Character { column: i + 1, found });
+        }
+        if self.height == 0 {
+            self.width = line.len();
+            self.words_per_row = self.width.div_ceil(WORD_BITS);
+        }
+        if line.len() != self.width {
+            return Err(RowError::Width { expected: self.width, found: line.len() });
+        }
+
+        let start = self.bits.len();
+        self.bits.resize(start + self.words_per_row, 0);
+        for (i, a_char) in line.bytes().enumerate() {
+            if a_char == b'#' {
+                self.bits[start + i / WORD_BITS] |= 1 << (i % WORD_BITS);
+            }
+        }
+        self.height += 1;
+        Ok(())
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    fn row(&self, row: usize) -> &[u64] {
+        &self.bits[row * self.words_per_row..(row + 1) * self.words_per_row]
+    }
+
+    /// Column is wrapped around the width of the forest.
+    pub fn is_tree(&self, row: usize, col: usize) -> bool {
+        let col = col % self.width;
+        (self.row(row)[col / WORD_BITS] >> (col % WORD_BITS)) & 1 == 1
+    }
+
+    /// Trees hit going `right` and `down` from the top left, or `None` if `down` is 0.
+    pub fn count_trees(&self, right: usize, down: usize) -> Option<u64> {
+        self.count_slopes(&[(right, down)]).map(|hits| hits[0])
+    }
+
+    /// Evaluates every (right, down) slope in a single pass over the rows. Gives `None` if
+    /// any slope has a `down` of 0, as it would never leave the top row.
+    pub fn count_slopes(&self, slopes: &[(usize, usize)]) -> Option<Vec<u64>> {
+        if slopes.iter().any(|(_, down)| *down == 0) {
+            return None;
+        }
+        if self.width == 0 {
+            return Some(vec![0; slopes.len()]);
+        }
+
+        let mut walkers: Vec<Walker> = slopes
+            .iter()
+            .map(|(right, down)| Walker { step: right % self.width, down: *down, wait: *down, col: 0, hits: 0 })
+            .collect();
+
+        if self.words_per_row == 1 {
+            for row in self.bits.iter().skip(1) {
+                for walker in walkers.iter_mut() {
+                    if walker.advance(self.width) {
+                        walker.hits += (row >> walker.col) & 1;
+                    }
+                }
+            }
+        } else {
+            for row in self.bits.chunks_exact(self.words_per_row).skip(1) {
+                for walker in walkers.iter_mut() {
+                    if walker.advance(self.width) {
+                        walker.hits += (row[walker.col / WORD_BITS] >> (walker.col % WORD_BITS)) & 1;
+                    }
+                }
+            }
+        }
+        Some(walkers.iter().map(|walker| walker.hits).collect())
+    }
+}
+
+/// Position of one slope while the rows are scanned.
+struct Walker {
+    step: usize,
+    down: usize,
+    wait: usize,
+    col: usize,
+    hits: u64
+}
+
+impl Walker {
+    /// Moves down a row, returns true if the slope lands on it. Counting down the rows
+    /// left avoids a division per row. `down` is never 0, `count_slopes` checks it.
+    fn advance(&mut self, width: usize) -> bool {
+        self.wait -= 1;
+        if self.wait != 0 {
+            return false;
+        }
+        self.wait = self.down;
+        self.col += self.step;
+        if self.col >= width {
+            self.col -= width;
+        }
+        true
+    }
+}
+
+impl Default for BitForest {
+    fn default() -> Self {
+        BitForest::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const EXAMPLE: [&str; 11] = [
+        "..##.......",
+        "#...#...#..",
+        ".#....#..#.",
+        "..#.#...#.#",
+        ".#...##..#.",
+        "..#.##.....",
+        ".#.#.#....#",
+        ".#........#",
+        "#.##...#...",
+        "#...##....#",
+        ".#..#...#.#",
+    ];
+
+    const SLOPES: [(usize, usize); 5] = [(1, 1), (3, 1), (5, 1), (7, 1), (1, 2)];
+
+    fn example_forest() -> BitForest {
+        let mut forest = BitForest::new();
+        for line in EXAMPLE.iter() {
+            forest.push_row(line).unwrap();
+        }
+        forest
+    }
+
+    #[test]
+    fn example_slopes() {
+        let forest = example_forest();
+        assert_eq!(forest.count_trees(3, 1), Some(7));
+        assert_eq!(forest.count_slopes(&SLOPES), Some(vec![2, 7, 3, 4, 2]));
+        // A slope that never moves down is refused rather than counted.
+        assert_eq!(forest.count_trees(3, 0), None);
+        assert_eq!(forest.count_slopes(&[(1, 1), (1, 0)]), None);
+    }
+
+    #[test]
+    fn matches_cell_rows() {
+        // Deterministic pseudo random rows, wide enough to check against `Horizontal`.
+        let mut seed: u32 = 12345;
+        let mut hill = vec![];
+        let mut forest = BitForest::new();
+        for _ in 0..500 {
+            let line: String = (0..CELL_WIDTH)
+                .map(|_| {
+                    seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                    if (seed >> 16).is_multiple_of(4) { '#' } else { '.' }
+                })
+                .collect();
+            forest.push_row(&line).unwrap();
+            hill.push(Horizontal::new(line));
+        }
+
+        let multi = forest.count_slopes(&SLOPES).unwrap();
+        for (i, (right, down)) in SLOPES.iter().enumerate() {
+            assert_eq!(count_trees(&hill, *right, *down), multi[i]);
+            assert_eq!(forest.count_trees(*right, *down), Some(multi[i]));
+        }
+    }
+
+    #[test]
+    fn wide_rows() {
+        let mut forest = BitForest::new();
+        let mut line = ".".repeat(130);
+        line.replace_range(129..130, "#");
+        forest.push_row(&line).unwrap();
+        forest.push_row(&line).unwrap();
+        assert!(forest.is_tree(0, 129));
+        assert!(forest.is_tree(1, 259));
+        assert!(!forest.is_tree(1, 128));
+        assert_eq!(forest.count_trees(129, 1), Some(1));
+    }
+
+    #[test]
+    fn bad_rows() {
+        let mut forest = BitForest::new();
+        forest.push_row("..#").unwrap();
+        assert_eq!(forest.push_row("..##"), Err(RowError::Width { expected: 3, found: 4 }));
+        assert_eq!(forest.push_row(""), Err(RowError::Width { expected: 3, found: 0 }));
+        assert_eq!(forest.push_row(".é."), Err(RowError::Character { column: 2, found: 'é' }));
+        assert_eq!(forest.push_row(".x.").unwrap_err().to_string(), "has 'x' at column 2");
+        assert_eq!(forest.height(), 1);
+    }
+}
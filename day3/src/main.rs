@@ -1,44 +1,8 @@
 use std::fs::File;
 use std::io::{self, BufRead, Error};
 use std::path::Path;
-use std::ops::Index;
-
-const CELL_WIDTH: usize = 31;
-
-struct Horizontal {
-    row: [Cell; CELL_WIDTH]
-}
-
-#[derive(Copy, Clone, std::cmp::PartialEq)]
-enum Cell {
-    Empty,
-    Tree
-}
-
-impl Horizontal {
-    fn new(line: String) -> Horizontal {
-        let mut horizontal = Horizontal { row: [Cell::Empty; CELL_WIDTH] };
-        let line_iter = line.chars();
-        for (i, a_char) in line_iter.enumerate() {
-            horizontal.row[i] = match a_char {
-                '.' => Cell::Empty,
-                '#' => Cell::Tree,
-                _ => panic!("Unexpected Character in input.")
-            };
-        }
-        
-        horizontal
-    }
-}
-
-impl Index<usize> for Horizontal {
-    type Output = Cell;
-
-    fn index(&self, i: usize) ->  &Self::Output {
-        &self.row[i % CELL_WIDTH]
-    }
-}
 
+use day3::{parse_move, BitForest};
 
 fn read_lines (filename: &Path) -> io::Result<io::Lines<io::BufReader<File>>> {
     let file = File::open(filename)?;
@@ -49,37 +13,41 @@ fn main() -> Result<(), Error>{
     let filename = Path::new("input.txt");
     let lines = read_lines(filename)?;
 
-    let mut hill: Vec<Horizontal> =  vec![];
+    let mut hill = BitForest::new();
 
-    for line in lines {
-        if let Ok(ip) = line {
-            let horizontal = Horizontal::new(ip);
-            hill.push(horizontal);   
+    for (i, line) in lines.enumerate() {
+        let line = line.map_err(|e| Error::new(e.kind(), format!("line {}: {}", i + 1, e)))?;
+        if line.trim().is_empty() {
+            continue;
         }
+        hill.push_row(line.trim())
+            .map_err(|e| Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", i + 1, e)))?;
     }
 
-    let mut hits = 0;
-    let mut horiz_posn = 0;
-
-    let vert: [u32; 5] = [1, 1, 1, 1, 2];
-    let horiz: [u32; 5] = [1, 3, 5, 7, 1];
-    let mut total: u64 = 1;
-    for (horiz_step, vert_step) in horiz.iter().zip(vert.iter()) {
-
-    
-        for row_idx in (*vert_step as usize..hill.len() as usize).step_by(*vert_step as usize) {
-            horiz_posn += *horiz_step as usize;
-            if hill[row_idx][horiz_posn] == Cell::Tree {
-                hits += 1;
+    // "route 1,1 2,1 3,1" finds the path with the fewest trees using any of the given moves.
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(|arg| arg.as_str()) == Some("route") {
+        let moves: Option<Vec<(usize, usize)>> = args[1..].iter().map(|arg| parse_move(arg)).collect();
+        let moves = match moves {
+            Some(moves) if !moves.is_empty() => moves,
+            _ => {
+                println!("Moves should be given as right,down pairs, e.g. route 1,1 2,1 3,1");
+                return Ok(());
             }
+        };
+        match hill.cheapest_route(&moves) {
+            Some(route) => {
+                println!("{}", hill.render_route(&route));
+                println!("The best route hits {} trees.", route.trees);
+            },
+            None => println!("No route reaches the bottom row with those moves.")
         }
-
-        horiz_posn = 0;
-        total *= hits;
-        hits = 0
-
+        return Ok(());
     }
 
+    let slopes: [(usize, usize); 5] = [(1, 1), (3, 1), (5, 1), (7, 1), (1, 2)];
+    let total: u64 = hill.count_slopes(&slopes).expect("every slope moves down").iter().product();
+
     println! ("There were {} hits.", total);
     Ok(())
 }
@@ -0,0 +1,163 @@
+use crate::BitForest;
+
+/// A path down the forest, one (row, column) per cell landed on. Columns keep counting
+/// to the right instead of wrapping, so the route can be drawn on the repeated map.
+#[derive(Debug, PartialEq)]
+pub struct Route {
+    pub cells: Vec<(usize, usize)>,
+    pub trees: u64
+}
+
+/// Parses a "right,down" move, e.g. "3,1".
+pub fn parse_move(text: &str) -> Option<(usize, usize)> {
+    let mut parts = text.split(',');
+    let right = parts.next()?.trim().parse().ok()?;
+    let down = parts.next()?.trim().parse().ok()?;
+    if parts.next().is_some() {
+        return None;
+    }
+    Some((right, down))
+}
+
+impl BitForest {
+    /// Finds the route from the top left to any cell of the bottom row that hits the fewest
+    /// trees, taking any of the (right, down) `moves` at every step. Like the slopes, the
+    /// starting cell is not counted. Every move has to go down at least one row, so the rows
+    /// can be relaxed in order instead of needing a priority queue.
+    pub fn cheapest_route(&self, moves: &[(usize, usize)]) -> Option<Route> {
+        if self.height() == 0 || self.width() == 0 || moves.iter().any(|(_, down)| *down == 0) {
+            return None;
+        }
+        let width = self.width();
+        let index = |row: usize, col: usize| row * width + col;
+
+        let mut cost: Vec<Option<u64>> = vec![None; self.height() * width];
+        let mut came_by: Vec<usize> = vec![0; self.height() * width];
+        cost[0] = Some(0);
+
+        for row in 0..self.height() {
+            for col in 0..width {
+                let here = match cost[index(row, col)] {
+                    Some(val) => val,
+                    None => continue
+                };
+                for (i, (right, down)) in moves.iter().enumerate() {
+                    let next_row = row + down;
+                    if next_row >= self.height() {
+                        continue;
+                    }
+                    let next_col = (col + right) % width;
+                    let next_cost = here + self.is_tree(next_row, next_col) as u64;
+                    let next = index(next_row, next_col);
+                    if cost[next].is_none_or(|val| next_cost < val) {
+                        cost[next] = Some(next_cost);
+                        came_by[next] = i;
+                    }
+                }
+            }
+        }
+
+        let last = self.height() - 1;
+        let (end_col, trees) = (0..width)
+            .filter_map(|col| cost[index(last, col)].map(|val| (col, val)))
+            .min_by_key(|(_, val)| *val)?;
+
+        // Walk back to the start, then replay the moves to get unwrapped columns.
+        let mut taken = vec![];
+        let (mut row, mut col) = (last, end_col);
+        while row != 0 {
+            let (right, down) = moves[came_by[index(row, col)]];
+            taken.push((right, down));
+            row -= down;
+            col = (col + width - right % width) % width;
+        }
+
+        let mut cells = vec![(0, 0)];
+        let (mut row, mut col) = (0, 0);
+        for (right, down) in taken.iter().rev() {
+            row += down;
+            col += right;
+            cells.push((row, col));
+        }
+
+        Some(Route { cells, trees })
+    }
+
+    /// Draws the map repeated wide enough to hold the route, with `O` where the route
+    /// lands on an open cell and `X` where it hits a tree. A forest of empty rows draws as
+    /// nothing.
+    pub fn render_route(&self, route: &Route) -> String {
+        if self.width() == 0 {
+            return String::new();
+        }
+        let max_col = route.cells.iter().map(|(_, col)| *col).max().unwrap_or(0);
+        let repeats = max_col / self.width() + 1;
+
+        let mut lines = vec![];
+        for row in 0..self.height() {
+            let mut line: Vec<char> = (0..self.width() * repeats)
+                .map(|col| if self.is_tree(row, col) { '#' } else { '.' })
+                .collect();
+            for (_, col) in route.cells.iter().filter(|(r, _)| *r == row) {
+                line[*col] = if line[*col] == '#' { 'X' } else { 'O' };
+            }
+            lines.push(line.into_iter().collect::<String>());
+        }
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn forest(rows: &[&str]) -> BitForest {
+        let mut forest = BitForest::new();
+        for row in rows.iter() {
+            forest.push_row(row).unwrap();
+        }
+        forest
+    }
+
+    #[test]
+    fn single_move_matches_slope() {
+        let forest = forest(&["..##...", "#...#..", ".#....#", "..#.#..", ".#...##"]);
+        let route = forest.cheapest_route(&[(3, 1)]).unwrap();
+        assert_eq!(Some(route.trees), forest.count_trees(3, 1));
+        assert_eq!(route.cells, vec![(0, 0), (1, 3), (2, 6), (3, 9), (4, 12)]);
+    }
+
+    #[test]
+    fn avoids_trees() {
+        let forest = forest(&["....", ".#..", "..#.", "...."]);
+        let route = forest.cheapest_route(&[(1, 1), (2, 1), (3, 1)]).unwrap();
+        assert_eq!(route.trees, 0);
+        let rendered = forest.render_route(&route);
+        assert!(!rendered.contains('X'));
+        assert_eq!(rendered.matches('O').count(), 4);
+    }
+
+    #[test]
+    fn empty_rows() {
+        let forest = forest(&[""]);
+        assert_eq!(forest.cheapest_route(&[(1, 1)]), None);
+        assert_eq!(forest.render_route(&Route { cells: vec![(0, 0)], trees: 0 }), "");
+    }
+
+    #[test]
+    fn unreachable_bottom() {
+        let forest = forest(&["..", "..", ".."]);
+        assert_eq!(forest.cheapest_route(&[(1, 2)]).map(|r| r.trees), Some(0));
+        assert_eq!(forest.cheapest_route(&[(1, 3)]), None);
+        assert_eq!(forest.cheapest_route(&[(1, 0)]), None);
+    }
+
+    #[test]
+    fn moves() {
+        assert_eq!(parse_move("3,1"), Some((3, 1)));
+        assert_eq!(parse_move(" 1 , 2"), Some((1, 2)));
+        assert_eq!(parse_move("3"), None);
+        assert_eq!(parse_move("3,1,1"), None);
+    }
+}
@@ -0,0 +1,396 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+
+use serde::de::{Deserializer, MapAccess, Visitor};
+use serde::{Deserialize, Serialize};
+
+use records::Records;
+
+use crate::schema::Schema;
+use crate::{Mode, Parsed, PassportRecord, Token};
+
+/// Column order for the standard keys, any other keys follow in alphabetical order.
+const KEY_ORDER: [&str; 8] = ["byr", "iyr", "eyr", "hgt", "hcl", "ecl", "pid", "cid"];
+
+/// Column holding the problems of a rejected record in CSV.
+const REASONS: &str = "reasons";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// The `key:value` puzzle input format, one record per line with blank lines between.
+    Text,
+    JsonLines,
+    /// One column per key. A missing key and a key with an empty value are both an empty
+    /// cell, so CSV cannot round trip a record like `hgt:`; it comes back without `hgt`.
+    Csv
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" | "txt" => Ok(Format::Text),
+            "jsonl" | "json" => Ok(Format::JsonLines),
+            "csv" => Ok(Format::Csv),
+            _ => Err(format!("Unknown format '{}', expected text, jsonl or csv", s))
+        }
+    }
+}
+
+/// Which records are exported.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Selection {
+    /// Every record, as parsed.
+    Raw,
+    /// Only the records that pass the schema.
+    Valid,
+    /// Only the records that fail the schema, with the reasons.
+    Rejected
+}
+
+impl std::str::FromStr for Selection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "raw" => Ok(Selection::Raw),
+            "valid" => Ok(Selection::Valid),
+            "rejected" => Ok(Selection::Rejected),
+            _ => Err(format!("Unknown selection '{}', expected raw, valid or rejected", s))
+        }
+    }
+}
+
+/// One exported passport. `reasons` is only present for rejected records.
+#[derive(Debug, Serialize, Deserialize)]
+struct Row {
+    fields: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reasons: Option<Vec<String>>
+}
+
+/// The fields of an imported JSON row in the order they were written, duplicates included,
+/// so they can be checked like the tokens of a text record.
+struct Pairs(Vec<(String, String)>);
+
+impl<'de> Deserialize<'de> for Pairs {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct PairsVisitor;
+
+        impl<'de> Visitor<'de> for PairsVisitor {
+            type Value = Pairs;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "an object of string fields")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Pairs, A::Error> {
+                let mut pairs = vec![];
+                while let Some(pair) = map.next_entry()? {
+                    pairs.push(pair);
+                }
+                Ok(Pairs(pairs))
+            }
+        }
+
+        deserializer.deserialize_map(PairsVisitor)
+    }
+}
+
+/// An imported JSON row. Any reasons are ignored, the records are checked again.
+#[derive(Deserialize)]
+struct ImportRow {
+    fields: Pairs
+}
+
+fn select_rows(records: &[PassportRecord], reasons: &[Vec<String>], selection: Selection) -> Vec<Row> {
+    records.iter()
+        .zip(reasons)
+        .filter_map(|(record, reasons)| {
+            let fields: BTreeMap<String, String> = record.map.iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect();
+            if selection == Selection::Raw {
+                return Some(Row { fields, reasons: None });
+            }
+
+            match (selection, reasons.is_empty()) {
+                (Selection::Valid, true) => Some(Row { fields, reasons: None }),
+                (Selection::Rejected, false) => Some(Row { fields, reasons: Some(reasons.clone()) }),
+                _ => None
+            }
+        })
+        .collect()
+}
+
+fn invalid_data<E>(e: E) -> io::Error where E: Into<Box<dyn std::error::Error + Send + Sync>> {
+    io::Error::new(ErrorKind::InvalidData, e)
+}
+
+/// Writes the selected records. `reasons` holds why each record was rejected, whether by the
+/// schema or while parsing, and is empty for valid records.
+pub fn export<W: Write>(records: &[PassportRecord], reasons: &[Vec<String>], selection: Selection, format: Format, writer: W) -> io::Result<()> {
+    let rows = select_rows(records, reasons, selection);
+    match format {
+        Format::Text => write_text(&rows, writer),
+        Format::JsonLines => write_jsonl(&rows, writer),
+        Format::Csv => write_csv(&rows, selection == Selection::Rejected, writer)
+    }
+}
+
+/// Reads records, checking their keys against `schema` in `mode` like the puzzle input.
+/// Each record comes with the line it starts on. Columns count characters in text and
+/// JSON Lines files, and fields in CSV files.
+pub fn import<R: Read>(format: Format, reader: R, schema: &Schema, mode: Mode) -> io::Result<Vec<(usize, Parsed)>> {
+    match format {
+        Format::Text => read_text(reader, schema, mode),
+        Format::JsonLines => read_jsonl(reader, schema, mode),
+        Format::Csv => read_csv(reader, schema, mode)
+    }
+}
+
+fn write_text<W: Write>(rows: &[Row], mut writer: W) -> io::Result<()> {
+    for (i, row) in rows.iter().enumerate() {
+        if i > 0 {
+            writeln!(writer)?;
+        }
+        let record = PassportRecord { map: row.fields.iter().map(|(k, v)| (k.clone(), v.clone())).collect() };
+        writeln!(writer, "{}", record.to_text())?;
+    }
+    Ok(())
+}
+
+fn read_text<R: Read>(reader: R, schema: &Schema, mode: Mode) -> io::Result<Vec<(usize, Parsed)>> {
+    Records::new(reader)
+        .map(|record| {
+            let record = record?;
+            Ok((record.line, PassportRecord::parse(&record.lines, record.line, schema, mode)))
+        })
+        .collect()
+}
+
+fn write_jsonl<W: Write>(rows: &[Row], mut writer: W) -> io::Result<()> {
+    for row in rows {
+        serde_json::to_writer(&mut writer, row)?;
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+fn read_jsonl<R: Read>(reader: R, schema: &Schema, mode: Mode) -> io::Result<Vec<(usize, Parsed)>> {
+    let mut records = vec![];
+    for (i, text) in BufReader::new(reader).lines().enumerate() {
+        let text = text?;
+        if text.trim().is_empty() {
+            continue;
+        }
+        let line = i + 1;
+        let row: ImportRow = serde_json::from_str(&text)
+            .map_err(|e| invalid_data(format!("line {}: {}", line, e)))?;
+
+        // Each key is looked for after the previous one, so repeated keys get their own column.
+        let mut from = 0;
+        let tokens = row.fields.0.into_iter()
+            .map(|(key, value)| {
+                let column = match text[from..].find(&format!("\"{}\"", key)) {
+                    Some(offset) => {
+                        from += offset + 1;
+                        text[..from - 1].chars().count() + 1
+                    },
+                    None => 1
+                };
+                Token { line, column, key, value }
+            })
+            .collect();
+        records.push((line, PassportRecord::from_tokens(tokens, vec![], schema, mode)));
+    }
+    Ok(records)
+}
+
+/// A column for every key used by any record, so records missing a key get an empty cell.
+fn columns(rows: &[Row]) -> Vec<String> {
+    let used: BTreeSet<&String> = rows.iter().flat_map(|row| row.fields.keys()).collect();
+    let mut columns: Vec<String> = KEY_ORDER.iter()
+        .filter(|key| used.iter().any(|k| k == *key))
+        .map(|key| key.to_string())
+        .collect();
+    columns.extend(used.into_iter().filter(|k| !KEY_ORDER.contains(&k.as_str())).cloned());
+    columns
+}
+
+fn write_csv<W: Write>(rows: &[Row], with_reasons: bool, writer: W) -> io::Result<()> {
+    let columns = columns(rows);
+    let mut writer = csv::Writer::from_writer(writer);
+
+    let mut header = columns.clone();
+    if with_reasons {
+        header.push(REASONS.to_string());
+    }
+    writer.write_record(&header)?;
+
+    for row in rows {
+        let mut cells: Vec<String> = columns.iter()
+            .map(|key| row.fields.get(key).cloned().unwrap_or_default())
+            .collect();
+        if with_reasons {
+            cells.push(row.reasons.as_ref().map(|r| r.join("; ")).unwrap_or_default());
+        }
+        writer.write_record(&cells)?;
+    }
+    writer.flush()
+}
+
+/// Every column but `reasons` is a field, and an empty cell means the key is missing, even
+/// if the exported record had the key with an empty value.
+fn read_csv<R: Read>(reader: R, schema: &Schema, mode: Mode) -> io::Result<Vec<(usize, Parsed)>> {
+    let mut reader = csv::Reader::from_reader(reader);
+    let header = reader.headers().map_err(invalid_data)?.clone();
+
+    let mut records = vec![];
+    for row in reader.records() {
+        let row = row.map_err(invalid_data)?;
+        let line = row.position().map(|p| p.line() as usize).unwrap_or(0);
+        let tokens = header.iter()
+            .zip(row.iter())
+            .enumerate()
+            .filter(|(_, (key, value))| *key != REASONS && !value.is_empty())
+            .map(|(i, (key, value))| Token { line, column: i + 1, key: key.to_string(), value: value.to_string() })
+            .collect();
+        records.push((line, PassportRecord::from_tokens(tokens, vec![], schema, mode)));
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const PASSPORTS: [&str; 3] = [
+        "byr:1937 cid:147 ecl:gry eyr:2020 hcl:#fffffd hgt:183cm iyr:2017 pid:860033327",
+        "byr:1929 cid:350 ecl:amb eyr:2023 hcl:#cfa07d iyr:2013 pid:028048884",
+        "byr:1931 ecl:brn eyr:2024 hcl:#ae17e1 hgt:179cm iyr:2013 pid:760753108 zzz:a,\"b\"",
+    ];
+
+    fn records() -> Vec<PassportRecord> {
+        PASSPORTS.iter().map(|text| PassportRecord::new(text).unwrap()).collect()
+    }
+
+    fn problems() -> Vec<Vec<String>> {
+        records().iter().map(|r| r.validate(&Schema::default()).iter().map(|p| p.to_string()).collect()).collect()
+    }
+
+    /// Imports leniently against the standard schema, expecting every record to parse.
+    fn imported(format: Format, input: &[u8]) -> io::Result<Vec<PassportRecord>> {
+        let parsed = import(format, input, &Schema::default(), Mode::Lenient)?;
+        Ok(parsed.into_iter().map(|(_, result)| result.ok().unwrap().0).collect())
+    }
+
+    fn round_trip(format: Format) {
+        let mut out = vec![];
+        export(&records(), &problems(), Selection::Raw, format, &mut out).unwrap();
+        let imported = imported(format, out.as_slice()).unwrap();
+        let text: Vec<String> = imported.iter().map(|r| r.to_text()).collect();
+        assert_eq!(text, PASSPORTS);
+    }
+
+    #[test]
+    fn text_round_trip() {
+        round_trip(Format::Text);
+    }
+
+    #[test]
+    fn jsonl_round_trip() {
+        round_trip(Format::JsonLines);
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        round_trip(Format::Csv);
+    }
+
+    #[test]
+    fn rejected_with_reasons() {
+        let mut out = vec![];
+        export(&records(), &problems(), Selection::Rejected, Format::JsonLines, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.lines().count(), 1);
+        assert!(out.contains("\"reasons\":[\"hgt is missing\"]"));
+
+        let mut out = vec![];
+        export(&records(), &problems(), Selection::Rejected, Format::Csv, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out, "byr,iyr,eyr,hcl,ecl,pid,cid,reasons\n1929,2013,2023,#cfa07d,amb,028048884,350,hgt is missing\n");
+        assert_eq!(imported(Format::Csv, out.as_bytes()).unwrap()[0].to_text(), PASSPORTS[1]);
+    }
+
+    #[test]
+    fn valid_only() {
+        let mut out = vec![];
+        export(&records(), &problems(), Selection::Valid, Format::Csv, &mut out).unwrap();
+        let imported = imported(Format::Csv, out.as_slice()).unwrap();
+        assert_eq!(imported.len(), 2);
+        assert!(imported.iter().all(|r| r.validate(&Schema::default()).is_empty()));
+    }
+
+    #[test]
+    fn empty_values() {
+        let records = vec![PassportRecord::new("byr:1937 hgt:").unwrap()];
+        assert_eq!(records[0].map["hgt"], "");
+        for format in &[Format::Text, Format::JsonLines] {
+            let mut out = vec![];
+            export(&records, &[vec![]], Selection::Raw, *format, &mut out).unwrap();
+            assert_eq!(imported(*format, out.as_slice()).unwrap()[0].map, records[0].map);
+        }
+
+        // CSV cannot tell an empty value from a missing key.
+        let mut out = vec![];
+        export(&records, &[vec![]], Selection::Raw, Format::Csv, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out.clone()).unwrap(), "byr,hgt\n1937,\n");
+        assert_eq!(imported(Format::Csv, out.as_slice()).unwrap()[0].to_text(), "byr:1937");
+    }
+
+    #[test]
+    fn bad_input() {
+        assert!(imported(Format::JsonLines, "{\"fields\": 3}".as_bytes()).is_err());
+        assert!(imported(Format::Csv, "byr,iyr\n1,2,3\n".as_bytes()).is_err());
+    }
+
+    fn messages(parsed: &[(usize, Parsed)]) -> Vec<String> {
+        parsed.iter()
+            .flat_map(|(_, result)| match result {
+                Ok((_, warnings)) => warnings.iter(),
+                Err((_, errors)) => errors.iter()
+            })
+            .map(|e| e.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn strict_import() {
+        let schema = Schema::default();
+        let jsonl = "{\"fields\":{\"byr\":\"1937\"}}\n{\"fields\":{\"hgt\":\"1\",\"zzz\":\"2\",\"hgt\":\"3\"}}\n";
+        let parsed = import(Format::JsonLines, jsonl.as_bytes(), &schema, Mode::Strict).unwrap();
+        assert!(parsed[0].1.is_ok());
+        assert_eq!(parsed[1].0, 2);
+        assert!(parsed[1].1.is_err());
+        assert_eq!(messages(&parsed), [
+            "line 2, column 22: unknown key zzz",
+            "line 2, column 32: duplicate key hgt, first seen at line 2, column 12",
+        ]);
+
+        let csv = "byr,zzz,byr\n1937,1,1938\n";
+        let parsed = import(Format::Csv, csv.as_bytes(), &schema, Mode::Lenient).unwrap();
+        assert_eq!(messages(&parsed), [
+            "line 2, column 2: unknown key zzz",
+            "line 2, column 3: duplicate key byr, first seen at line 2, column 1",
+        ]);
+        assert_eq!(parsed[0].1.as_ref().ok().unwrap().0.map["byr"], "1938");
+
+        // A custom schema decides which keys are known.
+        let custom = Schema::from_toml("optional = [\"zzz\"]\n[fields.pid]\nrule = \"digits\"\nlength = 9").unwrap();
+        let parsed = import(Format::Text, "zzz:1 byr:2".as_bytes(), &custom, Mode::Strict).unwrap();
+        assert_eq!(messages(&parsed), ["line 1, column 7: unknown key byr"]);
+    }
+}
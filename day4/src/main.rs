@@ -1,206 +1,348 @@
 use std::fs::File;
-use std::io::{self, BufRead, Error};
+use std::io::{self, Error, ErrorKind};
 use std::path::Path;
 use std::collections::HashMap;
 
-const REQUIRED_KEYS: [&'static str; 7]=["ecl", "pid", "eyr", "hcl", "byr", "iyr", "hgt"];
+use records::Records;
 
+mod export;
+mod passport;
+mod schema;
+
+use export::{Format, Selection};
+use passport::Passport;
+use schema::{Problem, Schema, Summary};
+
+#[derive(Debug)]
 struct PassportRecord {
     map: HashMap<String, String>
 }
 
-impl PassportRecord {
-    fn new(input_string: &String) -> Result<PassportRecord, &'static str> {
-        let mut record = PassportRecord{map: HashMap::new()};
-        
-        for part in input_string.split_whitespace() {
-            let parts:Vec<&str> = part.split(':').collect();
-            if parts.len() != 2 {
-                return Err("Failed to parse passport record");
-            }
-            record.map.insert(parts[0].to_string(), parts[1].to_string());
-        }
-
-        for key in REQUIRED_KEYS.iter() {
-            if !record.map.contains_key(*key) {
-                return Err("Missing required key.");
-            }
-        }
+/// How `PassportRecord::parse` treats duplicate and unknown keys.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    /// Duplicate and unknown keys reject the record.
+    Strict,
+    /// Duplicate and unknown keys are kept as warnings, a repeated key keeps its last value.
+    Lenient
+}
 
-        Ok(record)
-    }
+#[derive(Debug, PartialEq)]
+enum TokenProblem {
+    /// Not a `key:value` pair.
+    Malformed(String),
+    Duplicate { key: String, first_line: usize, first_column: usize },
+    Unknown(String)
+}
 
-    fn is_valid(&self) -> bool {
-        self.check_birth_year() &&
-        self.check_issue_year() &&
-        self.check_expiry_year() &&
-        self.check_height() &&
-        self.check_hair_colour() &&
-        self.check_eye_colour() &&
-        self.check_passport_id()
-    }
+/// A bad token in a record, with the line and column (both from 1) where it starts.
+#[derive(Debug, PartialEq)]
+struct ParseError {
+    line: usize,
+    column: usize,
+    problem: TokenProblem
+}
 
-    /// 4 digits, between 1920 and 2020 inclusive
-    fn check_birth_year(&self) -> bool {
-        if self.map["byr"].len() != 4 {
-            return false;
+impl std::fmt::Display for ParseError {
+    fn fmt (&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}, column {}: ", self.line, self.column)?;
+        match &self.problem {
+            TokenProblem::Malformed(token) => write!(f, "'{}' is not a key:value pair", token),
+            TokenProblem::Duplicate { key, first_line, first_column } => {
+                write!(f, "duplicate key {}, first seen at line {}, column {}", key, first_line, first_column)
+            },
+            TokenProblem::Unknown(key) => write!(f, "unknown key {}", key)
         }
-        let year = match self.map["byr"].parse::<u32>() {
-            Ok(val) => val,
-            Err(_) => 0,
-        };
-        if year < 1920 || year > 2020 {
-            return false;
-        }
-        true
     }
+}
 
-    //4 digits between 2010 and 2020 inclusive
-    fn check_issue_year(&self) -> bool {
-        if self.map["iyr"].len() != 4 {
-            return false;
-        }
-        let year = match self.map["iyr"].parse::<u32>() {
-            Ok(val) => val,
-            Err(_) => 0,
-        };
-        if year < 2010 || year > 2020 {
-            return false;
+/// A `key:value` pair and the line and column (both from 1) where it starts.
+struct Token {
+    line: usize,
+    column: usize,
+    key: String,
+    value: String
+}
+
+/// A parsed record with any warnings, or the record as far as it could be read with every
+/// error found in it.
+type Parsed = Result<(PassportRecord, Vec<ParseError>), (PassportRecord, Vec<ParseError>)>;
+
+impl PassportRecord {
+    /// Parses a single line leniently, giving the first malformed token as the error.
+    #[cfg(test)]
+    fn new(input_string: &str) -> Result<PassportRecord, ParseError> {
+        let lines = [input_string.to_string()];
+        match PassportRecord::parse(&lines, 1, Schema::standard(), Mode::Lenient) {
+            Ok((record, _)) => Ok(record),
+            Err((_, mut errors)) => Err(errors.remove(0))
         }
-        true
     }
 
-    fn check_expiry_year(&self) -> bool {
-        if self.map["eyr"].len() != 4 {
-            return false;
-        }
-        let year = match self.map["eyr"].parse::<u32>() {
-            Ok(val) => val,
-            Err(_) => 0,
-        };
-        if year < 2020 || year > 2030 {
-            return false;
+    /// Parses the lines of a record that starts at `first_line`. Malformed tokens are always
+    /// errors, duplicate keys and keys the schema does not know depend on the mode. Gives
+    /// the record and any warnings, or the readable part of the record and every error.
+    fn parse(lines: &[String], first_line: usize, schema: &Schema, mode: Mode) -> Parsed {
+        let mut tokens = vec![];
+        let mut errors = vec![];
+
+        for (i, line_text) in lines.iter().enumerate() {
+            let line = first_line + i;
+            for part in line_text.split_whitespace() {
+                let offset = part.as_ptr() as usize - line_text.as_ptr() as usize;
+                let column = line_text[..offset].chars().count() + 1;
+
+                let parts:Vec<&str> = part.split(':').collect();
+                if parts.len() != 2 {
+                    errors.push(ParseError { line, column, problem: TokenProblem::Malformed(part.to_string()) });
+                    continue;
+                }
+                tokens.push(Token { line, column, key: parts[0].to_string(), value: parts[1].to_string() });
+            }
         }
-        true
+
+        PassportRecord::from_tokens(tokens, errors, schema, mode)
     }
 
-    fn check_height(&self) -> bool {
-        let height = self.map["hgt"].clone();
-        if height.len() < 4 {
-            return false;
-        }
-        if height.ends_with("cm") {
-            let hnum = match height.trim_end_matches("cm").parse::<u32>() {
-                Ok(val) => val,
-                Err(_) => 0
-            };
-            if hnum >= 150 && hnum <= 193 {
-                return true;
+    /// Builds a record from its `key:value` pairs in the order they were read, on top of
+    /// any `errors` already found while reading them. Duplicate keys and keys the schema
+    /// does not know are errors or warnings depending on the mode.
+    fn from_tokens(tokens: Vec<Token>, mut errors: Vec<ParseError>, schema: &Schema, mode: Mode) -> Parsed {
+        let mut record = PassportRecord{map: HashMap::new()};
+        let mut seen: HashMap<String, (usize, usize)> = HashMap::new();
+        let mut warnings = vec![];
+
+        for Token { line, column, key, value } in tokens {
+            let mut found = vec![];
+            if let Some((first_line, first_column)) = seen.get(&key) {
+                found.push(TokenProblem::Duplicate {
+                    key: key.clone(),
+                    first_line: *first_line,
+                    first_column: *first_column
+                });
+            } else {
+                seen.insert(key.clone(), (line, column));
             }
-
-        } else if height.ends_with("in") {
-            let hnum = match height.trim_end_matches("in").parse::<u32>() {
-                Ok(val) => val,
-                Err(_) => 0
-            };
-            if hnum >= 59 && hnum <= 76 {
-                return true;
+            if !schema.is_known(&key) {
+                found.push(TokenProblem::Unknown(key.clone()));
+            }
+            for problem in found {
+                match mode {
+                    Mode::Strict => errors.push(ParseError { line, column, problem }),
+                    Mode::Lenient => warnings.push(ParseError { line, column, problem })
+                }
             }
-        } 
-        return false;
-    }
 
-    fn check_hair_colour(&self) -> bool {
-        if self.map["hcl"].len() != 7 {
-            return false;
+            record.map.insert(key, value);
         }
-        if self.map["hcl"].chars().nth(0).unwrap() != '#' {
-            return false
+
+        if errors.is_empty() {
+            Ok((record, warnings))
+        } else {
+            Err((record, errors))
         }
+    }
 
-        let mut value_check = self.map["hcl"].chars().all(|c| c.is_alphanumeric());
-        value_check |= !self.map["hcl"].chars().any(|c| matches! (c, 'A'..='Z'));
+    /// The record in the `key:value` input format, with the keys sorted.
+    fn to_text(&self) -> String {
+        let mut parts: Vec<String> = self.map.iter().map(|(k, v)| format!("{}:{}", k, v)).collect();
+        parts.sort();
+        parts.join(" ")
+    }
 
-        value_check
+    /// Every missing key and invalid field, empty if the passport is valid.
+    fn validate(&self, schema: &Schema) -> Vec<Problem> {
+        schema.validate(&self.map)
     }
 
-    fn check_eye_colour(&self) -> bool {
-        let eye_colours = ["amb", "blu", "brn", "gry", "grn", "hzl", "oth"];
-        eye_colours.iter().any(|&i| i == self.map["ecl"])
+    /// Problems for each of the records labelled with their line, from the checks on each
+    /// record followed by the checks over the whole batch.
+    fn validate_all(records: &[(usize, PassportRecord)], schema: &Schema) -> Vec<Vec<Problem>> {
+        let labelled: Vec<(usize, &HashMap<String, String>)> = records.iter()
+            .map(|(line, record)| (*line, &record.map))
+            .collect();
+        let batch = schema.validate_batch(&labelled);
+        records.iter()
+            .zip(batch)
+            .map(|((_, record), batch_problems)| {
+                let mut problems = record.validate(schema);
+                problems.extend(batch_problems);
+                problems
+            })
+            .collect()
     }
+}
+
+/// Command line: `day4 [schema.toml] [--strict] [--list-valid] [--import FILE]
+/// [--export text|jsonl|csv] [--select raw|valid|rejected]`.
+struct Options {
+    schema: Option<String>,
+    mode: Mode,
+    list_valid: bool,
+    import: Option<String>,
+    export: Option<Format>,
+    select: Selection
+}
 
-    fn check_passport_id(&self) -> bool {
-        self.map["pid"].len() == 9
+impl Options {
+    fn parse(args: &[String]) -> Result<Options, String> {
+        let mut options = Options { schema: None, mode: Mode::Lenient, list_valid: false, import: None, export: None, select: Selection::Raw };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+            match arg.as_str() {
+                "--strict" => options.mode = Mode::Strict,
+                "--list-valid" => options.list_valid = true,
+                "--import" => options.import = Some(value()?.clone()),
+                "--export" => options.export = Some(value()?.parse()?),
+                "--select" => options.select = value()?.parse()?,
+                _ if arg.starts_with("--") => return Err(format!("Unknown option {}", arg)),
+                _ => options.schema = Some(arg.clone())
+            }
+        }
+        Ok(options)
     }
+}
 
+/// A record that could not be parsed, as far as it could be read, with its errors.
+type Rejected = (usize, PassportRecord, Vec<ParseError>);
+
+/// The records that parsed and the ones that did not, each labelled with its line.
+type ReadRecords = (Vec<(usize, PassportRecord)>, Vec<Rejected>);
+
+/// Reads `input.txt`, or the text, JSON Lines or CSV file given to --import, checking keys
+/// against the schema in the chosen mode either way. Warnings and errors go to stderr so
+/// they never mix with exported data.
+fn read_records(options: &Options, schema: &Schema) -> Result<ReadRecords, Error> {
+    let parsed: Vec<(usize, Parsed)> = match &options.import {
+        Some(import_file) => {
+            let format = match Path::new(import_file).extension().and_then(|e| e.to_str()) {
+                Some(extension) => extension.parse().map_err(|e| Error::new(ErrorKind::InvalidInput, e))?,
+                None => Format::Text
+            };
+            export::import(format, File::open(import_file)?, schema, options.mode)?
+        },
+        None => Records::new(File::open(Path::new("input.txt"))?)
+            .map(|record| {
+                let record = record?;
+                Ok((record.line, PassportRecord::parse(&record.lines, record.line, schema, options.mode)))
+            })
+            .collect::<io::Result<_>>()?
+    };
+
+    let mut records: Vec<(usize, PassportRecord)> = vec!();
+    let mut rejected = vec![];
+    for (line, result) in parsed {
+        match result {
+            Ok((passport, warnings)) => {
+                for warning in warnings {
+                    eprintln!("Warning: {}", warning);
+                }
+                records.push((line, passport));
+            },
+            Err((passport, errors)) => {
+                for error in &errors {
+                    eprintln!("Rejected record at line {}: {}", line, error);
+                }
+                rejected.push((line, passport, errors));
+            }
+        };
+    }
+    Ok((records, rejected))
+}
 
+/// The records the schema found no problems with, typed where the types can hold them and
+/// as given otherwise, so there is one line for each valid passport.
+fn list_valid(records: &[(usize, PassportRecord)], all_problems: Vec<Vec<Problem>>) -> Vec<String> {
+    records.iter()
+        .zip(all_problems)
+        .filter(|(_, problems)| problems.is_empty())
+        .map(|((_, record), problems)| match Passport::checked(record, problems) {
+            Ok(passport) => passport.to_string(),
+            Err(_) => record.to_text()
+        })
+        .collect()
 }
 
 fn main() -> Result <(), Error> {
-    let filename = Path::new("input.txt");
-    let lines = read_lines(filename)?;
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = Options::parse(&args).map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+    let schema = match &options.schema {
+        Some(schema_file) => Schema::load(Path::new(schema_file))?,
+        None => Schema::default()
+    };
+
+    let (records, rejected) = read_records(&options, &schema)?;
+    let count = records.len() + rejected.len();
+
+    let all_problems = PassportRecord::validate_all(&records, &schema);
+
+    if let Some(format) = options.export {
+        // Records that could not be parsed go out in input order with their errors as reasons.
+        let mut rows: Vec<(usize, PassportRecord, Vec<String>)> = records.into_iter()
+            .zip(all_problems)
+            .map(|((line, record), problems)| (line, record, problems.iter().map(|p| p.to_string()).collect()))
+            .chain(rejected.into_iter().map(|(line, record, errors)| (line, record, errors.iter().map(|e| e.to_string()).collect())))
+            .collect();
+        rows.sort_by_key(|(line, _, _)| *line);
+        let (passports, reasons): (Vec<PassportRecord>, Vec<Vec<String>>) = rows.into_iter().map(|(_, record, reasons)| (record, reasons)).unzip();
+        return export::export(&passports, &reasons, options.select, format, io::stdout().lock());
+    }
 
-    let mut records: Vec<PassportRecord> = vec!(); 
-    let mut record_string = String::new();
-    let mut count = 0;
+    let mut summary = Summary::default();
+    let mut accepted = 0;
     let mut valid = 0;
-    for line in lines {
-        
-        if let Ok(ip) = line {
-            let trimmed = ip.trim_end();
-            if ip.trim_end() == "" {
-                println!("{}", record_string);
-                match PassportRecord::new(&record_string) {
-                    Ok(record) => records.push(record),
-                    Err(e) => println!("{}", e),
-                };
-                record_string.clear();
-                count += 1;
-            } else {
-                record_string.push_str(" ");
-                record_string.push_str(trimmed);
-            }
+    for ((line, _), problems) in records.iter().zip(all_problems.iter()) {
+        if problems.is_empty() {
+            valid += 1;
+        } else {
+            let text: Vec<String> = problems.iter().map(|p| p.to_string()).collect();
+            println!("Passport at line {}: {}", line, text.join("; "));
         }
+        if !problems.iter().any(|p| matches!(p, Problem::Missing { .. })) {
+            accepted += 1;
+        }
+        summary.add(problems);
     }
 
-    let total_length = records.len();
-    for record in records {
-        if record.is_valid() {
-            valid += 1;
+    print!("{}", summary);
+    println!("There are {} accepted passports from {} records. {} of those are valid.", accepted, count, valid);
+
+    if options.list_valid {
+        for line in list_valid(&records, all_problems) {
+            println!("{}", line);
         }
     }
 
-    println!("There are {} accepted passports from {} records. {} of those are valid.", total_length, count, valid);
-
     Ok(())
 }
 
-fn read_lines (filename: &Path) -> io::Result<io::Lines<io::BufReader<File>>> {
-    let file = File::open(filename)?;
-    Ok(io::BufReader::new(file).lines())
-}
-
 #[cfg(test)]
 mod tests {
 
     use super::*;
 
+    fn missing_keys(record: &PassportRecord) -> Vec<String> {
+        record.validate(&Schema::default())
+            .into_iter()
+            .filter_map(|p| match p {
+                Problem::Missing { key } => Some(key),
+                _ => None
+            })
+            .collect()
+    }
+
     #[test]
     fn case_good1() {
         let test = "ecl:gry pid:860033327 eyr:2020 hcl:#fffffd byr:1937 iyr:2017 cid:147 hgt:183cm";
-        let _ = PassportRecord::new(&test.to_string());
+        let record = PassportRecord::new(test).unwrap();
+        assert!(missing_keys(&record).is_empty());
     }
     #[test]
     fn case_bad1() {
         let test = "iyr:2013 ecl:amb cid:350 eyr:2023 pid:028048884        hcl:#cfa07d byr:1929";
-        let record = PassportRecord::new(&test.to_string());
-
-        if let Err(e) = record {
-            assert_eq! (e, "Missing required key.");
-        } else {
-            assert!(false);
-        }
-        
+        let record = PassportRecord::new(test).unwrap();
+        assert_eq! (missing_keys(&record), ["hgt"]);
     }
 
     #[test]    
@@ -213,11 +355,8 @@ mod tests {
         byr:1937 iyr:2017 cid:147 hgt:183cm"
         ];
         for test in test_array.iter() {
-        if let Ok(_) = PassportRecord::new(&test.to_string()) {
-            ()
-        } else {
-            assert!(false);
-        }
+        let record = PassportRecord::new(test).unwrap();
+        assert!(missing_keys(&record).is_empty());
     }
     }
     
@@ -227,15 +366,53 @@ mod tests {
         iyr:2011 ecl:brn hgt:59in",
         "iyr:2013 ecl:amb cid:350 eyr:2023 pid:028048884
         hcl:#cfa07d byr:1929"];
-        for test in test_array.iter() {
-        let record = PassportRecord::new(&test.to_string());
+        let expected = [["byr"], ["hgt"]];
+        for (test, missing) in test_array.iter().zip(expected.iter()) {
+        let record = PassportRecord::new(test).unwrap();
+        assert_eq! (missing_keys(&record), missing);
+    }
+    }
 
-        if let Err(e) = record {
-            assert_eq! (e, "Missing required key.");
-        } else {
-            assert!(false);
-        }
+    #[test]
+    fn bad_token() {
+        let record = PassportRecord::new("ecl:gry pid860033327");
+        assert_eq!(record.err(), Some(ParseError {
+            line: 1,
+            column: 9,
+            problem: TokenProblem::Malformed("pid860033327".to_string())
+        }));
+    }
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn duplicate_and_unknown_keys() {
+        let text = lines("hgt:170cm ecl:gry\n  zzz:1 hgt:183cm");
+        let schema = Schema::default();
+
+        let (record, errors) = PassportRecord::parse(&text, 5, &schema, Mode::Strict).err().unwrap();
+        let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(messages, [
+            "line 6, column 3: unknown key zzz",
+            "line 6, column 9: duplicate key hgt, first seen at line 5, column 1",
+        ]);
+        // The rejected record is still kept as read, for exporting with its errors.
+        assert_eq!(record.to_text(), "ecl:gry hgt:183cm zzz:1");
+
+        let (record, warnings) = PassportRecord::parse(&text, 5, &schema, Mode::Lenient).unwrap();
+        assert_eq!(warnings, errors);
+        assert_eq!(record.map["hgt"], "183cm");
+        assert_eq!(record.map["zzz"], "1");
     }
+
+    #[test]
+    fn strict_accepts_clean_record() {
+        let text = lines("ecl:gry pid:860033327 eyr:2020\nhcl:#fffffd byr:1937 iyr:2017 cid:147 hgt:183cm");
+        let (record, warnings) = PassportRecord::parse(&text, 1, &Schema::default(), Mode::Strict).unwrap();
+        assert!(warnings.is_empty());
+        assert!(record.validate(&Schema::default()).is_empty());
     }
 
     #[test]
@@ -251,18 +428,15 @@ mod tests {
         "iyr:2010 hgt:158cm hcl:#b6652a ecl:blu byr:1944 eyr:2021 pid:093154719"
         ];
         for test in test_array.iter() {
-        if let Ok(record) = PassportRecord::new(&test.to_string()) {
-            assert!(record.check_birth_year());
-            assert!(record.check_issue_year());
-            assert!(record.check_expiry_year());
-            assert!(record.check_height());
-            assert!(record.check_hair_colour());
-            assert!(record.check_eye_colour());
-            assert!(record.check_passport_id());
-            
-            assert!(record.is_valid())
+        if let Ok(record) = PassportRecord::new(test) {
+            let schema = Schema::default();
+            for (key, rule) in schema.fields() {
+                assert!(rule.check(&record.map[key]).is_ok(), "{} failed", key);
+            }
+
+            assert!(record.validate(&schema).is_empty())
         } else {
-            assert!(false);
+            panic!("Record should parse.");
         }
     }
     }
@@ -281,12 +455,38 @@ mod tests {
         pid:3556412378 byr:2007"
         ];
         for test in test_array.iter() {
-            if let Ok(record) = PassportRecord::new(&test.to_string()) {
-                assert!(!record.is_valid())
+            if let Ok(record) = PassportRecord::new(test) {
+                assert!(!record.validate(&Schema::default()).is_empty())
             } else {
-                assert!(false);
+                panic!("Record should parse.");
             }
         }
     }
-    
-}
\ No newline at end of file
+
+    #[test]
+    fn list_valid_follows_schema() {
+        let text = "pid:087499704 hgt:1880mm ecl:grn iyr:2012 eyr:2030 byr:1980 hcl:#623a2f cid:1
+
+pid:087499705 hgt:74in ecl:grn iyr:2012 eyr:2030 byr:1980 hcl:#623a2f cid:1
+
+pid:087499706 hgt:74in ecl:grn iyr:2012 eyr:2030 byr:1980 hcl:#623a2f cid:2";
+        let records: Vec<(usize, PassportRecord)> = Records::new(text.as_bytes())
+            .map(|record| {
+                let record = record.unwrap();
+                (record.line, PassportRecord::parse(&record.lines, record.line, &Schema::default(), Mode::Lenient).unwrap().0)
+            })
+            .collect();
+
+        let standard = include_str!("../schema.toml");
+        let millimetres = Schema::from_toml(&standard.replace("cm = [150, 193], in = [59, 76]", "mm = [1500, 1930]")).unwrap();
+        let problems = PassportRecord::validate_all(&records, &millimetres);
+        assert_eq!(problems.iter().filter(|p| p.is_empty()).count(), 1);
+        assert_eq!(list_valid(&records, problems), [records[0].1.to_text()]);
+
+        let unique_cid = Schema::from_toml(&format!("{}\n[[batch_rules]]\nrule = \"unique\"\nkey = \"cid\"", standard)).unwrap();
+        let listed = list_valid(&records, PassportRecord::validate_all(&records, &unique_cid));
+        // The second passport repeats the cid of the first, so only the third is valid.
+        assert_eq!(listed.len(), 1);
+        assert!(listed[0].contains("pid:087499706"));
+    }
+}
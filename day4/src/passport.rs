@@ -0,0 +1,268 @@
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use crate::schema::{Problem, Schema};
+use crate::PassportRecord;
+
+/// A passport that passed its rules, with every field parsed into its own type. It is built
+/// through `Passport::try_from(record)` against the standard rules, or `Passport::checked`
+/// with what the active schema found, so the fields are private.
+#[derive(Debug, PartialEq)]
+pub struct Passport {
+    birth_year: u16,
+    issue_year: u16,
+    expiry_year: u16,
+    height: Height,
+    hair_colour: Rgb,
+    eye_colour: EyeColour,
+    /// Digit values, so leading zeroes are kept.
+    passport_id: [u8; 9],
+    country_id: Option<String>
+}
+
+impl Passport {
+    pub fn birth_year(&self) -> u16 {
+        self.birth_year
+    }
+
+    pub fn issue_year(&self) -> u16 {
+        self.issue_year
+    }
+
+    pub fn expiry_year(&self) -> u16 {
+        self.expiry_year
+    }
+
+    pub fn height(&self) -> Height {
+        self.height
+    }
+
+    pub fn hair_colour(&self) -> Rgb {
+        self.hair_colour
+    }
+
+    pub fn eye_colour(&self) -> EyeColour {
+        self.eye_colour
+    }
+
+    /// Digit values, so leading zeroes are kept.
+    pub fn passport_id(&self) -> [u8; 9] {
+        self.passport_id
+    }
+
+    pub fn country_id(&self) -> Option<&str> {
+        self.country_id.as_deref()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HeightUnit {
+    Cm,
+    In
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Height {
+    pub value: u16,
+    pub unit: HeightUnit
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EyeColour {
+    Amber,
+    Blue,
+    Brown,
+    Grey,
+    Green,
+    Hazel,
+    Other
+}
+
+impl FromStr for Height {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (value, unit) = if let Some(value) = s.strip_suffix("cm") {
+            (value, HeightUnit::Cm)
+        } else if let Some(value) = s.strip_suffix("in") {
+            (value, HeightUnit::In)
+        } else {
+            return Err("is not a height in cm or in");
+        };
+        let value = value.parse().map_err(|_| "is not a height in cm or in")?;
+        Ok(Height { value, unit })
+    }
+}
+
+impl FromStr for Rgb {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits = s.strip_prefix('#').filter(|d| d.len() == 6 && d.chars().all(|c| c.is_ascii_hexdigit()))
+            .ok_or("is not an rgb colour")?;
+        let channel = |i: usize| u8::from_str_radix(&digits[i..i + 2], 16).map_err(|_| "is not an rgb colour");
+        Ok(Rgb { r: channel(0)?, g: channel(2)?, b: channel(4)? })
+    }
+}
+
+impl FromStr for EyeColour {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "amb" => Ok(EyeColour::Amber),
+            "blu" => Ok(EyeColour::Blue),
+            "brn" => Ok(EyeColour::Brown),
+            "gry" => Ok(EyeColour::Grey),
+            "grn" => Ok(EyeColour::Green),
+            "hzl" => Ok(EyeColour::Hazel),
+            "oth" => Ok(EyeColour::Other),
+            _ => Err("is not an eye colour")
+        }
+    }
+}
+
+fn parse_passport_id(s: &str) -> Result<[u8; 9], &'static str> {
+    let mut id = [0; 9];
+    if s.len() != id.len() {
+        return Err("is not a nine digit passport id");
+    }
+    for (digit, c) in id.iter_mut().zip(s.chars()) {
+        *digit = c.to_digit(10).ok_or("is not a nine digit passport id")? as u8;
+    }
+    Ok(id)
+}
+
+/// Parses a field that the schema already accepted. Failing here would mean the schema and
+/// the types disagree, which is still reported as a problem rather than a panic.
+fn field<T>(record: &PassportRecord, key: &str, parse: fn(&str) -> Result<T, &'static str>) -> Result<T, Vec<Problem>> {
+    let value = record.map.get(key).map(|v| v.as_str()).unwrap_or("");
+    parse(value).map_err(|reason| vec![Problem::Invalid {
+        key: key.to_string(),
+        value: value.to_string(),
+        rule: "type",
+        reason: reason.to_string()
+    }])
+}
+
+impl Passport {
+    /// Types a record given every problem the active schema found with it, batch rules
+    /// included, so nothing is checked a second time against other rules. Any problem
+    /// rejects the record, as does a value the schema accepted that the types cannot hold.
+    pub fn checked(record: &PassportRecord, problems: Vec<Problem>) -> Result<Passport, Vec<Problem>> {
+        if !problems.is_empty() {
+            return Err(problems);
+        }
+
+        Ok(Passport {
+            birth_year: field(record, "byr", |s| s.parse().map_err(|_| "is not a year"))?,
+            issue_year: field(record, "iyr", |s| s.parse().map_err(|_| "is not a year"))?,
+            expiry_year: field(record, "eyr", |s| s.parse().map_err(|_| "is not a year"))?,
+            height: field(record, "hgt", str::parse)?,
+            hair_colour: field(record, "hcl", str::parse)?,
+            eye_colour: field(record, "ecl", str::parse)?,
+            passport_id: field(record, "pid", parse_passport_id)?,
+            country_id: record.map.get("cid").cloned()
+        })
+    }
+}
+
+impl TryFrom<PassportRecord> for Passport {
+    type Error = Vec<Problem>;
+
+    fn try_from(record: PassportRecord) -> Result<Self, Self::Error> {
+        Passport::checked(&record, record.validate(Schema::standard()))
+    }
+}
+
+impl std::fmt::Display for Height {
+    fn fmt (&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let unit = match self.unit {
+            HeightUnit::Cm => "cm",
+            HeightUnit::In => "in"
+        };
+        write!(f, "{}{}", self.value, unit)
+    }
+}
+
+impl std::fmt::Display for Rgb {
+    fn fmt (&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
+impl std::fmt::Display for EyeColour {
+    fn fmt (&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let code = match self {
+            EyeColour::Amber => "amb",
+            EyeColour::Blue => "blu",
+            EyeColour::Brown => "brn",
+            EyeColour::Grey => "gry",
+            EyeColour::Green => "grn",
+            EyeColour::Hazel => "hzl",
+            EyeColour::Other => "oth"
+        };
+        write!(f, "{}", code)
+    }
+}
+
+/// Writes the passport back in the `key:value` input format.
+impl std::fmt::Display for Passport {
+    fn fmt (&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let pid: String = self.passport_id().iter().map(|d| char::from(b'0' + d)).collect();
+        write!(f, "byr:{} iyr:{} eyr:{} hgt:{} hcl:{} ecl:{} pid:{}",
+            self.birth_year(), self.issue_year(), self.expiry_year(), self.height(),
+            self.hair_colour(), self.eye_colour(), pid)?;
+        if let Some(cid) = self.country_id() {
+            write!(f, " cid:{}", cid)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn typed_fields() {
+        let record = PassportRecord::new("pid:087499704 hgt:74in ecl:grn iyr:2012 eyr:2030 byr:1980 hcl:#623a2f").unwrap();
+        let passport = Passport::try_from(record).unwrap();
+        assert_eq!(passport.birth_year(), 1980);
+        assert_eq!(passport.height(), Height { value: 74, unit: HeightUnit::In });
+        assert_eq!(passport.hair_colour(), Rgb { r: 0x62, g: 0x3a, b: 0x2f });
+        assert_eq!(passport.eye_colour(), EyeColour::Green);
+        assert_eq!(passport.passport_id(), [0, 8, 7, 4, 9, 9, 7, 0, 4]);
+        assert_eq!(passport.country_id(), None);
+        assert_eq!(passport.to_string(), "byr:1980 iyr:2012 eyr:2030 hgt:74in hcl:#623a2f ecl:grn pid:087499704");
+    }
+
+    #[test]
+    fn rejected() {
+        let record = PassportRecord::new("eyr:1972 cid:100 hcl:#18171d ecl:amb hgt:170 pid:186cm iyr:2018 byr:1926").unwrap();
+        let problems = Passport::try_from(record).unwrap_err();
+        let keys: Vec<String> = problems.iter().map(|p| p.rule()).collect();
+        assert_eq!(keys, ["eyr year", "hgt measure", "pid digits"]);
+    }
+
+    #[test]
+    fn checked() {
+        let record = PassportRecord::new("pid:087499704 hgt:74in ecl:grn iyr:2012 eyr:2030 byr:1980 hcl:#623a2f").unwrap();
+        assert!(Passport::checked(&record, vec![]).is_ok());
+        // Problems found by other rules, such as batch rules, are kept as they are.
+        let duplicate = Problem::NotUnique { key: "pid".to_string(), value: "087499704".to_string(), first: 1 };
+        assert_eq!(Passport::checked(&record, vec![duplicate]).unwrap_err().len(), 1);
+
+        let record = PassportRecord::new("pid:087499704 hgt:1880mm ecl:grn iyr:2012 eyr:2030 byr:1980 hcl:#623a2f").unwrap();
+        let problems = Passport::checked(&record, vec![]).unwrap_err();
+        assert_eq!(problems[0].rule(), "hgt type");
+    }
+}
@@ -0,0 +1,584 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+
+/// The rule set in `schema.toml`, built into the binary.
+const DEFAULT_SCHEMA: &str = include_str!("../schema.toml");
+
+/// How a single passport field is checked.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum Rule {
+    /// Four digits, between min and max inclusive.
+    Year { min: u32, max: u32 },
+    /// A number followed by a unit, with an inclusive range for each unit.
+    Measure { units: BTreeMap<String, [u32; 2]> },
+    /// A `#` followed by six lowercase hex digits.
+    HexColour,
+    /// One of a fixed list of values.
+    OneOf { values: Vec<String> },
+    /// Exactly `length` digits, leading zeroes included.
+    Digits { length: usize },
+}
+
+impl Rule {
+    /// Short name of the kind of rule, used to group failures.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rule::Year { .. } => "year",
+            Rule::Measure { .. } => "measure",
+            Rule::HexColour => "hex colour",
+            Rule::OneOf { .. } => "one of",
+            Rule::Digits { .. } => "digits",
+        }
+    }
+
+    /// Err holds the reason the value breaks the rule, e.g. "exceeds 59..76 for in".
+    pub fn check(&self, value: &str) -> Result<(), String> {
+        match self {
+            Rule::Year { min, max } => {
+                if value.len() != 4 || !all_digits(value) {
+                    return Err("is not a four digit year".to_string());
+                }
+                let year: u32 = value.parse().map_err(|_| "is not a four digit year".to_string())?;
+                check_range(year, *min, *max, "")
+            },
+            Rule::Measure { units } => {
+                let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+                let (number, unit) = value.split_at(split);
+                let range = units.get(unit).ok_or_else(|| {
+                    let expected: Vec<&str> = units.keys().map(|k| k.as_str()).collect();
+                    format!("has unit '{}', expected one of {}", unit, expected.join(", "))
+                })?;
+                let number: u32 = number.parse().map_err(|_| "has no number".to_string())?;
+                check_range(number, range[0], range[1], &format!(" for {}", unit))
+            },
+            Rule::HexColour => {
+                let hex = value.strip_prefix('#').is_some_and(|digits| {
+                    digits.len() == 6 && digits.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f'))
+                });
+                if hex {
+                    Ok(())
+                } else {
+                    Err("is not # followed by six lowercase hex digits".to_string())
+                }
+            },
+            Rule::OneOf { values } => {
+                if values.iter().any(|v| v == value) {
+                    Ok(())
+                } else {
+                    Err(format!("is not one of {}", values.join(", ")))
+                }
+            },
+            Rule::Digits { length } => {
+                if !all_digits(value) {
+                    Err("contains characters other than digits".to_string())
+                } else if value.len() != *length {
+                    Err(format!("has {} digits, expected {}", value.len(), length))
+                } else {
+                    Ok(())
+                }
+            },
+        }
+    }
+}
+
+fn all_digits(value: &str) -> bool {
+    value.chars().all(|c| c.is_ascii_digit())
+}
+
+fn check_range(number: u32, min: u32, max: u32, suffix: &str) -> Result<(), String> {
+    if number < min {
+        Err(format!("is below {}..{}{}", min, max, suffix))
+    } else if number > max {
+        Err(format!("exceeds {}..{}{}", min, max, suffix))
+    } else {
+        Ok(())
+    }
+}
+
+/// A rule over several fields of one passport.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum RecordRule {
+    /// The year in `later` is at least `min_gap` years after the year in `earlier`.
+    YearGap { later: String, earlier: String, min_gap: u32 },
+}
+
+impl RecordRule {
+    /// Fields that are missing or not numbers are left to the field rules to report. A gap
+    /// that goes past the largest year fails the rule.
+    fn check(&self, record: &HashMap<String, String>) -> Option<Problem> {
+        match self {
+            RecordRule::YearGap { later, earlier, min_gap } => {
+                let later_year: u32 = record.get(later)?.parse().ok()?;
+                let earlier_year: u32 = record.get(earlier)?.parse().ok()?;
+                if earlier_year.checked_add(*min_gap).is_some_and(|min_later| later_year >= min_later) {
+                    return None;
+                }
+                Some(Problem::Inconsistent {
+                    rule: format!("{} after {}", later, earlier),
+                    reason: format!("{}={} is less than {} years after {}={}",
+                        later, later_year, min_gap, earlier, earlier_year)
+                })
+            }
+        }
+    }
+}
+
+/// A rule over every passport in the batch.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum BatchRule {
+    /// No two passports share a value for `key`. Passports without the key are ignored.
+    Unique { key: String },
+}
+
+impl BatchRule {
+    /// Adds the problems of each passport to the matching entry of `problems`.
+    fn check(&self, records: &[(usize, &HashMap<String, String>)], problems: &mut [Vec<Problem>]) {
+        match self {
+            BatchRule::Unique { key } => {
+                let mut first_seen: HashMap<&String, usize> = HashMap::new();
+                for (i, (line, record)) in records.iter().enumerate() {
+                    let value = match record.get(key) {
+                        Some(value) => value,
+                        None => continue
+                    };
+                    match first_seen.get(value) {
+                        Some(first) => problems[i].push(Problem::NotUnique {
+                            key: key.clone(),
+                            value: value.clone(),
+                            first: *first
+                        }),
+                        None => {
+                            first_seen.insert(value, *line);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Something wrong with a single passport.
+#[derive(Debug, PartialEq)]
+pub enum Problem {
+    Missing { key: String },
+    Invalid { key: String, value: String, rule: &'static str, reason: String },
+    /// Broke a `RecordRule`.
+    Inconsistent { rule: String, reason: String },
+    /// Repeats the value of the passport labelled `first`.
+    NotUnique { key: String, value: String, first: usize },
+}
+
+impl Problem {
+    /// The rule that was broken, as counted by `Summary`.
+    pub fn rule(&self) -> String {
+        match self {
+            Problem::Missing { key } => format!("{} missing", key),
+            Problem::Invalid { key, rule, .. } => format!("{} {}", key, rule),
+            Problem::Inconsistent { rule, .. } => rule.clone(),
+            Problem::NotUnique { key, .. } => format!("{} unique", key),
+        }
+    }
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt (&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Problem::Missing { key } => write!(f, "{} is missing", key),
+            Problem::Invalid { key, value, reason, .. } => write!(f, "{}={} {}", key, value, reason),
+            Problem::Inconsistent { reason, .. } => write!(f, "{}", reason),
+            Problem::NotUnique { key, value, first } => {
+                write!(f, "{}={} is also used by the passport at line {}", key, value, first)
+            },
+        }
+    }
+}
+
+/// Failure counts per broken rule over a batch of passports.
+#[derive(Debug, Default)]
+pub struct Summary {
+    counts: BTreeMap<String, usize>
+}
+
+impl Summary {
+    pub fn add(&mut self, problems: &[Problem]) {
+        for problem in problems {
+            *self.counts.entry(problem.rule()).or_insert(0) += 1;
+        }
+    }
+
+    #[cfg(test)]
+    pub fn count(&self, rule: &str) -> usize {
+        self.counts.get(rule).copied().unwrap_or(0)
+    }
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt (&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (rule, count) in self.counts.iter() {
+            writeln!(f, "{:>16}: {}", rule, count)?;
+        }
+        Ok(())
+    }
+}
+
+/// The set of required passport fields and the rule for each, and the optional keys.
+#[derive(Debug, Deserialize)]
+pub struct Schema {
+    #[serde(default = "default_optional")]
+    optional: Vec<String>,
+    fields: BTreeMap<String, Rule>,
+    #[serde(default)]
+    record_rules: Vec<RecordRule>,
+    #[serde(default)]
+    batch_rules: Vec<BatchRule>
+}
+
+fn default_optional() -> Vec<String> {
+    vec!["cid".to_string()]
+}
+
+impl Schema {
+    /// The built in rules, parsed once. The typed `Passport` is checked against these.
+    pub fn standard() -> &'static Schema {
+        static STANDARD: OnceLock<Schema> = OnceLock::new();
+        STANDARD.get_or_init(Schema::default)
+    }
+
+    pub fn from_toml(text: &str) -> Result<Schema, toml::de::Error> {
+        toml::from_str(text)
+    }
+
+    pub fn load(filename: &Path) -> io::Result<Schema> {
+        let text = fs::read_to_string(filename)?;
+        Schema::from_toml(&text).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+    }
+
+    #[cfg(test)]
+    pub fn fields(&self) -> impl Iterator<Item = (&String, &Rule)> {
+        self.fields.iter()
+    }
+
+    /// True for a required or optional key.
+    pub fn is_known(&self, key: &str) -> bool {
+        self.fields.contains_key(key) || self.optional.iter().any(|k| k == key)
+    }
+
+    /// Every missing key, every field that breaks its rule and every broken record rule.
+    pub fn validate(&self, record: &HashMap<String, String>) -> Vec<Problem> {
+        let mut problems = vec![];
+        for (key, rule) in self.fields.iter() {
+            match record.get(key) {
+                None => problems.push(Problem::Missing { key: key.clone() }),
+                Some(value) => {
+                    if let Err(reason) = rule.check(value) {
+                        problems.push(Problem::Invalid {
+                            key: key.clone(),
+                            value: value.clone(),
+                            rule: rule.name(),
+                            reason
+                        });
+                    }
+                }
+            }
+        }
+        problems.extend(self.record_rules.iter().filter_map(|rule| rule.check(record)));
+        problems
+    }
+
+    /// Problems from the batch rules, one list per record. Each record is labelled with its
+    /// line number, which is used to point back to the first use of a repeated value.
+    pub fn validate_batch(&self, records: &[(usize, &HashMap<String, String>)]) -> Vec<Vec<Problem>> {
+        let mut problems: Vec<Vec<Problem>> = records.iter().map(|_| vec![]).collect();
+        for rule in self.batch_rules.iter() {
+            rule.check(records, &mut problems);
+        }
+        problems
+    }
+}
+
+impl Default for Schema {
+    fn default() -> Self {
+        Schema::from_toml(DEFAULT_SCHEMA).expect("Built in schema.toml is invalid.")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn default_schema() {
+        let schema = Schema::default();
+        let keys: Vec<&String> = schema.fields().map(|(key, _)| key).collect();
+        assert_eq!(keys, ["byr", "ecl", "eyr", "hcl", "hgt", "iyr", "pid"]);
+        assert!(schema.is_known("cid"));
+        assert!(!schema.is_known("zzz"));
+    }
+
+    fn rule<'a>(schema: &'a Schema, key: &str) -> &'a Rule {
+        schema.fields().find(|(k, _)| *k == key).unwrap().1
+    }
+
+    #[test]
+    fn rules() {
+        let year = Rule::Year { min: 1920, max: 2020 };
+        assert!(year.check("2002").is_ok());
+        assert_eq!(year.check("2021"), Err("exceeds 1920..2020".to_string()));
+        assert_eq!(year.check("1919"), Err("is below 1920..2020".to_string()));
+        assert!(year.check("+999").is_err());
+
+        let schema = Schema::default();
+        let height = rule(&schema, "hgt");
+        assert!(height.check("60in").is_ok());
+        assert!(height.check("190cm").is_ok());
+        assert_eq!(height.check("190in"), Err("exceeds 59..76 for in".to_string()));
+        assert_eq!(height.check("190"), Err("has unit '', expected one of cm, in".to_string()));
+        assert_eq!(height.check("cm"), Err("has no number".to_string()));
+
+        assert!(Rule::HexColour.check("#123abc").is_ok());
+        assert!(Rule::HexColour.check("#123abz").is_err());
+        assert!(Rule::HexColour.check("#zzzzzz").is_err());
+        assert!(Rule::HexColour.check("#123ABC").is_err());
+        assert!(Rule::HexColour.check("123abc").is_err());
+        assert!(Rule::HexColour.check("").is_err());
+        assert!(Rule::HexColour.check("é123abc").is_err());
+
+        let eye = Rule::OneOf { values: vec!["brn".to_string()] };
+        assert!(eye.check("brn").is_ok());
+        assert!(eye.check("wat").is_err());
+
+        let digits = Rule::Digits { length: 9 };
+        assert!(digits.check("000000001").is_ok());
+        assert_eq!(digits.check("0123456789"), Err("has 10 digits, expected 9".to_string()));
+        assert!(digits.check("12345678a").is_err());
+        assert!(digits.check("+12345678").is_err());
+        assert!(digits.check("").is_err());
+        assert_eq!(rule(&schema, "pid").name(), "digits");
+    }
+
+    #[test]
+    fn problems() {
+        let schema = Schema::default();
+        let record: HashMap<String, String> = [("hgt", "190in"), ("pid", "0123456789"), ("ecl", "brn")]
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let problems = schema.validate(&record);
+        let text: Vec<String> = problems.iter().map(|p| p.to_string()).collect();
+        assert_eq!(text, [
+            "byr is missing",
+            "eyr is missing",
+            "hcl is missing",
+            "hgt=190in exceeds 59..76 for in",
+            "iyr is missing",
+            "pid=0123456789 has 10 digits, expected 9",
+        ]);
+
+        let mut summary = Summary::default();
+        summary.add(&problems);
+        summary.add(&problems[..2]);
+        assert_eq!(summary.count("byr missing"), 2);
+        assert_eq!(summary.count("hgt measure"), 1);
+        assert_eq!(summary.count("ecl one of"), 0);
+    }
+
+    fn record(text: &str) -> HashMap<String, String> {
+        text.split_whitespace()
+            .map(|part| {
+                let (key, value) = part.split_once(':').unwrap();
+                (key.to_string(), value.to_string())
+            })
+            .collect()
+    }
+
+    const CONSISTENCY: &str = "
+        [[record_rules]]
+        rule = \"year_gap\"
+        later = \"iyr\"
+        earlier = \"byr\"
+        min_gap = 16
+
+        [[record_rules]]
+        rule = \"year_gap\"
+        later = \"eyr\"
+        earlier = \"iyr\"
+        min_gap = 1
+
+        [[batch_rules]]
+        rule = \"unique\"
+        key = \"cid\"
+
+        [fields.byr]
+        rule = \"year\"
+        min = 1920
+        max = 2020
+    ";
+
+    #[test]
+    fn record_rules() {
+        let schema = Schema::from_toml(CONSISTENCY).unwrap();
+        assert!(schema.validate(&record("byr:1980 iyr:2010 eyr:2020")).is_empty());
+
+        let problems = schema.validate(&record("byr:2000 iyr:2010 eyr:2010"));
+        let text: Vec<String> = problems.iter().map(|p| p.to_string()).collect();
+        assert_eq!(text, [
+            "iyr=2010 is less than 16 years after byr=2000",
+            "eyr=2010 is less than 1 years after iyr=2010",
+        ]);
+        assert_eq!(problems[0].rule(), "iyr after byr");
+
+        // Missing or broken years are only reported by the field rules.
+        let problems = schema.validate(&record("byr:20x0 eyr:2010"));
+        assert_eq!(problems.len(), 1);
+
+        // Years no field rule has checked do not overflow the gap.
+        let problems = schema.validate(&record("byr:4294967290 iyr:4294967295"));
+        assert!(problems.iter().any(|p| p.rule() == "iyr after byr"));
+    }
+
+    #[test]
+    fn batch_rules() {
+        let schema = Schema::from_toml(CONSISTENCY).unwrap();
+        let records = [record("cid:1"), record("cid:2"), record("byr:1990"), record("cid:1"), record("cid:1")];
+        let labelled: Vec<(usize, &HashMap<String, String>)> = records.iter()
+            .enumerate()
+            .map(|(i, r)| (i * 2 + 1, r))
+            .collect();
+        let problems = schema.validate_batch(&labelled);
+        assert_eq!(problems.iter().map(|p| p.len()).collect::<Vec<usize>>(), [0, 0, 0, 1, 1]);
+        assert_eq!(problems[3][0].to_string(), "cid=1 is also used by the passport at line 1");
+        assert_eq!(problems[4][0].rule(), "cid unique");
+
+        assert!(Schema::default().validate_batch(&labelled).iter().all(|p| p.is_empty()));
+    }
+
+    #[test]
+    fn custom_schema() {
+        let schema = Schema::from_toml("
+            [fields.hgt]
+            rule = \"measure\"
+            units = { mm = [1500, 1930] }
+        ").unwrap();
+        let (_, height) = schema.fields().next().unwrap();
+        assert!(height.check("1600mm").is_ok());
+        assert!(height.check("160cm").is_err());
+        assert!(schema.is_known("hgt"));
+        assert!(schema.is_known("cid"));
+        assert!(!schema.is_known("byr"));
+
+        assert!(Schema::from_toml("[fields.byr]\nrule = \"decade\"").is_err());
+    }
+
+    /// Property tests for every field of the built in schema, checking that generated
+    /// valid values pass, generated invalid values fail and nothing ever panics.
+    mod properties {
+
+        use super::*;
+        use proptest::prelude::*;
+
+        const EYE_COLOURS: [&str; 7] = ["amb", "blu", "brn", "gry", "grn", "hzl", "oth"];
+        const YEARS: [(&str, u32, u32); 3] = [("byr", 1920, 2020), ("iyr", 2010, 2020), ("eyr", 2020, 2030)];
+
+        fn check(key: &str, value: &str) -> Result<(), String> {
+            rule(&Schema::default(), key).check(value)
+        }
+
+        fn valid_height() -> impl Strategy<Value = String> {
+            prop_oneof![
+                (150u32..=193).prop_map(|h| format!("{}cm", h)),
+                (59u32..=76).prop_map(|h| format!("{}in", h)),
+            ]
+        }
+
+        fn invalid_height() -> impl Strategy<Value = String> {
+            prop_oneof![
+                (0u32..150).prop_map(|h| format!("{}cm", h)),
+                (194u32..100_000).prop_map(|h| format!("{}cm", h)),
+                (0u32..59).prop_map(|h| format!("{}in", h)),
+                (77u32..100_000).prop_map(|h| format!("{}in", h)),
+                (0u32..1000).prop_map(|h| h.to_string()),
+                (0u32..1000, "[a-z]{1,3}")
+                    .prop_filter("known unit", |(_, unit)| unit != "cm" && unit != "in")
+                    .prop_map(|(h, unit)| format!("{}{}", h, unit)),
+                "(cm|in)",
+            ]
+        }
+
+        fn invalid_hair_colour() -> impl Strategy<Value = String> {
+            prop_oneof![
+                "[0-9a-f]{6}",
+                "#[0-9a-f]{0,5}",
+                "#[0-9a-f]{7,9}",
+                "#[0-9a-f]{0,5}[g-zA-Z#][0-9a-f]{0,5}"
+                    .prop_filter("six digits", |hcl| hcl.len() == 7),
+            ]
+        }
+
+        fn invalid_passport_id() -> impl Strategy<Value = String> {
+            prop_oneof![
+                "[0-9]{0,8}",
+                "[0-9]{10,12}",
+                "[0-9]{0,8}[^0-9][0-9]{0,8}",
+            ]
+        }
+
+        proptest! {
+            #[test]
+            fn years_in_range(field in 0..YEARS.len(), offset in 0u32..=100) {
+                let (key, min, max) = YEARS[field];
+                let year = min + offset % (max - min + 1);
+                prop_assert!(check(key, &year.to_string()).is_ok());
+            }
+
+            #[test]
+            fn years_out_of_range(field in 0..YEARS.len(), year in 0u32..100_000) {
+                let (key, min, max) = YEARS[field];
+                prop_assume!(year < min || year > max);
+                prop_assert!(check(key, &year.to_string()).is_err());
+                let padded = format!("{:05}", year);
+                prop_assert!(check(key, &padded).is_err());
+            }
+
+            #[test]
+            fn heights(valid in valid_height(), invalid in invalid_height()) {
+                prop_assert!(check("hgt", &valid).is_ok());
+                prop_assert!(check("hgt", &invalid).is_err());
+            }
+
+            #[test]
+            fn hair_colours(valid in "#[0-9a-f]{6}", invalid in invalid_hair_colour()) {
+                prop_assert!(check("hcl", &valid).is_ok());
+                prop_assert!(check("hcl", &invalid).is_err());
+            }
+
+            #[test]
+            fn eye_colours(valid in prop::sample::select(&EYE_COLOURS[..]), invalid in "[a-z]{0,4}") {
+                prop_assert!(check("ecl", valid).is_ok());
+                prop_assume!(!EYE_COLOURS.contains(&invalid.as_str()));
+                prop_assert!(check("ecl", &invalid).is_err());
+            }
+
+            #[test]
+            fn passport_ids(valid in "[0-9]{9}", invalid in invalid_passport_id()) {
+                prop_assert!(check("pid", &valid).is_ok());
+                prop_assert!(check("pid", &invalid).is_err());
+            }
+
+            #[test]
+            fn never_panics(value in any::<String>()) {
+                for (_, rule) in Schema::default().fields() {
+                    let _ = rule.check(&value);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::BTreeSet;
+
+use crate::layout::Layout;
+use crate::seat_map::SeatMap;
+use crate::Seat;
+
+/// How seats are picked for new passengers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Policy {
+    /// Lowest free seat ids first.
+    FirstFree,
+    /// Free seats in the first or last column first, then the rest, each by id.
+    WindowFirst,
+    /// Every passenger in the same row, next to each other if possible.
+    Together
+}
+
+impl std::str::FromStr for Policy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "first" => Ok(Policy::FirstFree),
+            "window" => Ok(Policy::WindowFirst),
+            "together" | "group" => Ok(Policy::Together),
+            _ => Err(format!("Unknown policy '{}', expected first, window or together", s))
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AllocError {
+    NotEnough { wanted: usize, free: usize },
+    /// No single row has enough free seats for the group.
+    NoRow { wanted: usize }
+}
+
+impl std::fmt::Display for AllocError {
+    fn fmt (&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AllocError::NotEnough { wanted, free } => {
+                write!(f, "wanted {} seats but only {} are free", wanted, free)
+            },
+            AllocError::NoRow { wanted } => write!(f, "no row has {} free seats", wanted)
+        }
+    }
+}
+
+/// Hands out the empty seats of a seat map. Only seats between the first and last occupied
+/// seat are free, the same rule that finds our own seat, so seats missing from the front
+/// and back of the aircraft are never issued.
+pub struct Allocator {
+    layout: Layout,
+    free: BTreeSet<u32>
+}
+
+impl Allocator {
+    pub fn new(layout: &Layout, map: &SeatMap) -> Allocator {
+        Allocator { layout: layout.clone(), free: map.gaps().into_iter().flatten().collect() }
+    }
+
+    pub fn free(&self) -> usize {
+        self.free.len()
+    }
+
+    /// Picks seats for `passengers` people and marks them taken. Nothing is taken if the
+    /// seats cannot all be found.
+    pub fn assign(&mut self, passengers: usize, policy: Policy) -> Result<Vec<Seat>, AllocError> {
+        if passengers > self.free.len() {
+            return Err(AllocError::NotEnough { wanted: passengers, free: self.free.len() });
+        }
+
+        let ids: Vec<u32> = match policy {
+            Policy::FirstFree => self.free.iter().copied().take(passengers).collect(),
+            Policy::WindowFirst => {
+                let last_col = self.layout.cols() - 1;
+                let (window, aisle): (Vec<u32>, Vec<u32>) = self.free.iter()
+                    .partition(|id| matches!(self.layout.seat_of(**id), Some((_, col)) if col == 0 || col == last_col));
+                window.into_iter().chain(aisle).take(passengers).collect()
+            },
+            Policy::Together => self.together(passengers).ok_or(AllocError::NoRow { wanted: passengers })?
+        };
+
+        for id in &ids {
+            self.free.remove(id);
+        }
+        Ok(ids.into_iter().map(|id| self.issue(id)).collect())
+    }
+
+    /// The first row with a long enough run of free seats, or failing that the first row
+    /// with enough free seats anywhere in it.
+    fn together(&self, passengers: usize) -> Option<Vec<u32>> {
+        if passengers == 0 {
+            return Some(vec![]);
+        }
+        let rows: Vec<Vec<u32>> = (0..self.layout.rows())
+            .map(|row| (0..self.layout.cols())
+                .map(|col| self.layout.id(row, col))
+                .filter(|id| self.free.contains(id))
+                .collect())
+            .collect();
+
+        let adjacent = rows.iter().find_map(|free| {
+            free.windows(passengers)
+                .find(|run| run[passengers - 1] - run[0] == passengers as u32 - 1)
+                .map(|run| run.to_vec())
+        });
+        adjacent.or_else(|| rows.into_iter()
+            .find(|free| free.len() >= passengers)
+            .map(|free| free[..passengers].to_vec()))
+    }
+
+    fn issue(&self, id: u32) -> Seat {
+        let (row, col) = self.layout.seat_of(id).expect("free seats are on the aircraft");
+        Seat { code: self.layout.encode(row, col), row, col, id }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn small() -> Layout {
+        Layout::new(2, 2, ('F', 'B'), ('L', 'R')).unwrap()
+    }
+
+    /// Free seats are 5, 6, 8, 9 and 11; seats 0 and 15 are missing.
+    fn allocator() -> Allocator {
+        let map = SeatMap::new(&small(), [1, 2, 3, 4, 7, 10, 12, 13, 14].iter().copied());
+        Allocator::new(&small(), &map)
+    }
+
+    fn ids(seats: &[Seat]) -> Vec<u32> {
+        seats.iter().map(|seat| seat.id).collect()
+    }
+
+    #[test]
+    fn first_free() {
+        let mut allocator = allocator();
+        assert_eq!(allocator.free(), 5);
+        let seats = allocator.assign(2, Policy::FirstFree).unwrap();
+        assert_eq!(ids(&seats), [5, 6]);
+        assert_eq!(seats[0].code, "FBLR");
+        assert_eq!(ids(&allocator.assign(1, Policy::FirstFree).unwrap()), [8]);
+        assert_eq!(allocator.free(), 2);
+    }
+
+    #[test]
+    fn window_first() {
+        let mut allocator = allocator();
+        assert_eq!(ids(&allocator.assign(3, Policy::WindowFirst).unwrap()), [8, 11, 5]);
+    }
+
+    #[test]
+    fn together() {
+        let mut allocator = allocator();
+        // Row 1 has 5 and 6 next to each other.
+        assert_eq!(ids(&allocator.assign(2, Policy::Together).unwrap()), [5, 6]);
+        // Row 2 has 8, 9 and 11 free but not in a run of three.
+        assert_eq!(ids(&allocator.assign(3, Policy::Together).unwrap()), [8, 9, 11]);
+        assert_eq!(allocator.free(), 0);
+    }
+
+    #[test]
+    fn failures_take_nothing() {
+        let mut allocator = allocator();
+        assert_eq!(allocator.assign(4, Policy::Together).unwrap_err(), AllocError::NoRow { wanted: 4 });
+        assert_eq!(allocator.assign(6, Policy::FirstFree).unwrap_err(), AllocError::NotEnough { wanted: 6, free: 5 });
+        assert_eq!(allocator.free(), 5);
+    }
+}
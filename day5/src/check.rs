@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::Seat;
+
+/// Why a boarding pass that decoded fine still cannot be right.
+#[derive(Debug, PartialEq)]
+pub enum PassProblem {
+    /// Another pass for the same seat was seen first, on `first_line`.
+    Duplicate { first_line: usize },
+    /// The row does not exist on this aircraft.
+    OutsideRows { row: u32, valid: Range<u32> }
+}
+
+impl std::fmt::Display for PassProblem {
+    fn fmt (&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PassProblem::Duplicate { first_line } => write!(f, "is the same seat as line {}", first_line),
+            PassProblem::OutsideRows { row, valid } => {
+                write!(f, "is in row {}, valid rows are {}..={}", row, valid.start, valid.end.saturating_sub(1))
+            }
+        }
+    }
+}
+
+/// Parses valid rows given as `first..end` or `first..=last`.
+pub fn parse_rows(s: &str) -> Result<Range<u32>, String> {
+    let error = || format!("Bad row range '{}', expected first..end or first..=last", s);
+    let (first, end) = if let Some((first, last)) = s.split_once("..=") {
+        let last = last.parse::<u32>().map_err(|_| error())?;
+        (first, last.checked_add(1).ok_or_else(error)?)
+    } else {
+        let (first, end) = s.split_once("..").ok_or_else(error)?;
+        (first, end.parse().map_err(|_| error())?)
+    };
+    Ok(first.parse().map_err(|_| error())?..end)
+}
+
+/// Checks the decoded `(line, seat)` passes against each other and the valid rows. Gives
+/// the line and problem of every pass that should not count as an occupied seat.
+pub fn check_seats(seats: &[(usize, Seat)], valid_rows: &Range<u32>) -> Vec<(usize, PassProblem)> {
+    let mut first_lines: HashMap<u32, usize> = HashMap::new();
+    let mut problems = vec![];
+    for (line, seat) in seats {
+        if !valid_rows.contains(&seat.row) {
+            problems.push((*line, PassProblem::OutsideRows { row: seat.row, valid: valid_rows.clone() }));
+        } else if let Some(first_line) = first_lines.get(&seat.id) {
+            problems.push((*line, PassProblem::Duplicate { first_line: *first_line }));
+        } else {
+            first_lines.insert(seat.id, *line);
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn seats(codes: &[&str]) -> Vec<(usize, Seat)> {
+        codes.iter().enumerate().map(|(i, code)| (i + 1, Seat::new(code.to_string()).unwrap())).collect()
+    }
+
+    #[test]
+    fn duplicates_and_rows() {
+        let seats = seats(&["FBFBBFFRLR", "FFFFFFFLLL", "BFFFBBFRRR", "FBFBBFFRLR", "FBFBBFFRLR"]);
+        let problems = check_seats(&seats, &(1..127));
+        assert_eq!(problems, vec![
+            (2, PassProblem::OutsideRows { row: 0, valid: 1..127 }),
+            (4, PassProblem::Duplicate { first_line: 1 }),
+            (5, PassProblem::Duplicate { first_line: 1 }),
+        ]);
+        assert_eq!(problems[0].1.to_string(), "is in row 0, valid rows are 1..=126");
+        assert!(check_seats(&seats[..3], &(0..128)).is_empty());
+    }
+
+    #[test]
+    fn row_ranges() {
+        assert_eq!(parse_rows("4..124"), Ok(4..124));
+        assert_eq!(parse_rows("4..=123"), Ok(4..124));
+        assert!(parse_rows("4-123").is_err());
+        assert!(parse_rows("..=x").is_err());
+        assert!(parse_rows("0..=4294967295").is_err());
+        assert_eq!(parse_rows("0..=4294967294"), Ok(0..u32::MAX));
+    }
+}
@@ -0,0 +1,223 @@
+/// Describes how boarding pass codes map to seats on an aircraft. A code is `row_bits`
+/// row letters followed by `col_bits` column letters. Each letter picks the lower or upper
+/// half of the remaining range, which is the same as reading it as a binary digit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layout {
+    row_bits: u32,
+    col_bits: u32,
+    /// Letters for the (lower, upper) half of the rows.
+    row_letters: (char, char),
+    /// Letters for the (lower, upper) half of the columns.
+    col_letters: (char, char)
+}
+
+/// The most row and column bits a layout can have between them.
+const MAX_BITS: u32 = 31;
+
+impl Default for Layout {
+    /// 128 rows picked with F/B and 8 columns picked with L/R.
+    fn default() -> Self {
+        Layout { row_bits: 7, col_bits: 3, row_letters: ('F', 'B'), col_letters: ('L', 'R') }
+    }
+}
+
+impl Layout {
+    /// Seat ids must fit in a `u32`, so there can be at most 31 bits in all, and each pair of
+    /// letters must be two different letters.
+    pub fn new(row_bits: u32, col_bits: u32, row_letters: (char, char), col_letters: (char, char)) -> Result<Layout, LayoutError> {
+        if row_bits == 0 || col_bits == 0 {
+            return Err(LayoutError::NoBits);
+        }
+        if row_bits.saturating_add(col_bits) > MAX_BITS {
+            return Err(LayoutError::TooManyBits { bits: row_bits.saturating_add(col_bits) });
+        }
+        for letters in [row_letters, col_letters].iter() {
+            if letters.0 == letters.1 {
+                return Err(LayoutError::SameLetters { letter: letters.0 });
+            }
+        }
+        Ok(Layout { row_bits, col_bits, row_letters, col_letters })
+    }
+
+    pub fn rows(&self) -> u32 {
+        1 << self.row_bits
+    }
+
+    pub fn cols(&self) -> u32 {
+        1 << self.col_bits
+    }
+
+    pub fn code_len(&self) -> usize {
+        (self.row_bits + self.col_bits) as usize
+    }
+
+    /// Seat ids run along each row, then on to the next row.
+    pub fn id(&self, row: u32, col: u32) -> u32 {
+        (row << self.col_bits) | col
+    }
+
+    /// The (row, col) of a seat id, if the id is on the aircraft.
+    pub fn seat_of(&self, id: u32) -> Option<(u32, u32)> {
+        if id >= self.rows() * self.cols() {
+            return None;
+        }
+        Some((id >> self.col_bits, id & (self.cols() - 1)))
+    }
+
+    /// Gives the (row, col) of a code, or what is wrong with it.
+    pub fn decode(&self, code: &str) -> Result<(u32, u32), CodeError> {
+        let length = code.chars().count();
+        if length != self.code_len() {
+            return Err(CodeError::Length { expected: self.code_len(), found: length });
+        }
+
+        let mut row = 0;
+        let mut col = 0;
+        for (i, letter) in code.chars().enumerate() {
+            let (value, letters) = if (i as u32) < self.row_bits {
+                (&mut row, self.row_letters)
+            } else {
+                (&mut col, self.col_letters)
+            };
+            let bit = if letter == letters.0 {
+                0
+            } else if letter == letters.1 {
+                1
+            } else {
+                return Err(CodeError::Letter { position: i + 1, found: letter, expected: letters });
+            };
+            *value = (*value << 1) | bit;
+        }
+        Ok((row, col))
+    }
+
+    /// The boarding pass code for a seat.
+    pub fn encode(&self, row: u32, col: u32) -> String {
+        let letter = |value: u32, bit: u32, letters: (char, char)| {
+            if (value >> bit) & 1 == 0 { letters.0 } else { letters.1 }
+        };
+        let row_code = (0..self.row_bits).rev().map(|bit| letter(row, bit, self.row_letters));
+        let col_code = (0..self.col_bits).rev().map(|bit| letter(col, bit, self.col_letters));
+        row_code.chain(col_code).collect()
+    }
+}
+
+/// Parses the (lower, upper) letters for rows or columns, given as two letters like `FB`.
+pub fn parse_letters(s: &str) -> Result<(char, char), String> {
+    let mut letters = s.chars();
+    match (letters.next(), letters.next(), letters.next()) {
+        (Some(lower), Some(upper), None) => Ok((lower, upper)),
+        _ => Err(format!("Bad letters '{}', expected two letters like FB", s))
+    }
+}
+
+/// Why a layout cannot be used.
+#[derive(Debug, PartialEq)]
+pub enum LayoutError {
+    /// Rows or columns have no bits.
+    NoBits,
+    /// Seat ids of this many bits do not fit in a `u32`.
+    TooManyBits { bits: u32 },
+    /// The lower and upper half are both picked with `letter`.
+    SameLetters { letter: char }
+}
+
+impl std::fmt::Display for LayoutError {
+    fn fmt (&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LayoutError::NoBits => write!(f, "rows and columns need at least one bit each"),
+            LayoutError::TooManyBits { bits } => write!(f, "{} bits is too many, at most {} fit", bits, MAX_BITS),
+            LayoutError::SameLetters { letter } => write!(f, "'{}' cannot pick both halves", letter)
+        }
+    }
+}
+
+/// Why a boarding pass code could not be decoded.
+#[derive(Debug, PartialEq)]
+pub enum CodeError {
+    Length { expected: usize, found: usize },
+    /// `position` counts letters from 1.
+    Letter { position: usize, found: char, expected: (char, char) }
+}
+
+impl std::fmt::Display for CodeError {
+    fn fmt (&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CodeError::Length { expected, found } => {
+                write!(f, "has {} letters, expected {}", found, expected)
+            },
+            CodeError::Letter { position, found, expected } => {
+                write!(f, "has '{}' at position {}, expected {} or {}", found, position, expected.0, expected.1)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn examples() {
+        let layout = Layout::default();
+        assert_eq!(layout.decode("BFFFBBFRRR"), Ok((70, 7)));
+        assert_eq!(layout.decode("FFFBBBFRRR"), Ok((14, 7)));
+        assert_eq!(layout.decode("BBFFBBFRLL"), Ok((102, 4)));
+        assert_eq!(layout.id(102, 4), 820);
+        assert_eq!(layout.encode(44, 5), "FBFBBFFRLR");
+    }
+
+    #[test]
+    fn round_trip_every_seat() {
+        let small = Layout::new(3, 2, ('U', 'D'), ('<', '>')).unwrap();
+        for layout in [Layout::default(), small].iter() {
+            let mut ids = vec![];
+            for row in 0..layout.rows() {
+                for col in 0..layout.cols() {
+                    let code = layout.encode(row, col);
+                    assert_eq!(code.chars().count(), layout.code_len());
+                    assert_eq!(layout.decode(&code), Ok((row, col)));
+                    ids.push(layout.id(row, col));
+                    assert_eq!(layout.seat_of(layout.id(row, col)), Some((row, col)));
+                }
+            }
+            let expected: Vec<u32> = (0..layout.rows() * layout.cols()).collect();
+            assert_eq!(ids, expected);
+            assert_eq!(layout.seat_of(layout.rows() * layout.cols()), None);
+        }
+    }
+
+    #[test]
+    fn bad_codes() {
+        let layout = Layout::default();
+        assert_eq!(layout.decode("FBFBBFFRL"), Err(CodeError::Length { expected: 10, found: 9 }));
+        assert_eq!(layout.decode(""), Err(CodeError::Length { expected: 10, found: 0 }));
+        // Multibyte letters are counted as one letter, not sliced through.
+        assert_eq!(layout.decode("FBFBBFFRLé"),
+            Err(CodeError::Letter { position: 10, found: 'é', expected: ('L', 'R') }));
+        let error = layout.decode("FBFXBFFRLR").unwrap_err();
+        assert_eq!(error.to_string(), "has 'X' at position 4, expected F or B");
+        // Column letters are not accepted for rows, or the other way round.
+        assert!(layout.decode("FBFBBFRRLR").is_err());
+        assert!(layout.decode("FBFBBFFRLB").is_err());
+    }
+
+    #[test]
+    fn new_layouts() {
+        assert_eq!(Layout::new(7, 3, ('F', 'B'), ('L', 'R')), Ok(Layout::default()));
+        assert!(Layout::new(16, 15, ('F', 'B'), ('L', 'R')).is_ok());
+        assert_eq!(Layout::new(16, 16, ('F', 'B'), ('L', 'R')), Err(LayoutError::TooManyBits { bits: 32 }));
+        assert_eq!(Layout::new(u32::MAX, 1, ('F', 'B'), ('L', 'R')), Err(LayoutError::TooManyBits { bits: u32::MAX }));
+        assert_eq!(Layout::new(0, 3, ('F', 'B'), ('L', 'R')), Err(LayoutError::NoBits));
+        assert_eq!(Layout::new(7, 3, ('F', 'B'), ('L', 'L')), Err(LayoutError::SameLetters { letter: 'L' }));
+
+        let big = Layout::new(16, 15, ('F', 'B'), ('L', 'R')).unwrap();
+        assert_eq!(big.seat_of(u32::MAX), None);
+        assert_eq!(big.seat_of(big.id(65535, 32767)), Some((65535, 32767)));
+
+        assert_eq!(parse_letters("UD"), Ok(('U', 'D')));
+        assert!(parse_letters("U").is_err());
+        assert!(parse_letters("UDX").is_err());
+    }
+}
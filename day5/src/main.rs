@@ -1,9 +1,21 @@
+use std::collections::HashSet;
 use std::fs::File;
 use std::io::{self, BufRead, Error};
+use std::ops::Range;
 use std::path::Path;
 use std::cmp::Ordering;
 
-#[derive(Eq)]
+mod allocator;
+mod check;
+mod layout;
+mod seat_map;
+
+use allocator::{Allocator, Policy};
+use check::{check_seats, parse_rows};
+use layout::{parse_letters, CodeError, Layout};
+use seat_map::SeatMap;
+
+#[derive(Debug, Eq)]
 struct Seat {
     code: String,
     row: u32,
@@ -12,37 +24,18 @@ struct Seat {
 }
 
 impl Seat {
-    fn new (code: String) -> Result<Seat, Error> {
-        let row_code = &code[0..7];
-        let col_code = &code[7..10];
-
-        let mut row_max = 127;
-        let mut row_min = 0;
-        for side in row_code.chars() {
-            let half = (row_max - row_min)/2;
-            if side == 'F' {
-                row_max = row_min + half;
-            } else {
-                row_min = row_min + half+1;
-            }
-        }
-
-        let mut col_max = 7;
-        let mut col_min = 0;
-        for side in col_code.chars() {
-            let half = (col_max - col_min)/2;
-            if side == 'L' {
-                col_max = col_min + half;
-            } else {
-                col_min = col_min + half+1;
-            }
-        }
+    #[cfg(test)]
+    fn new (code: String) -> Result<Seat, CodeError> {
+        Seat::with_layout(code, &Layout::default())
+    }
 
+    fn with_layout (code: String, layout: &Layout) -> Result<Seat, CodeError> {
+        let (row, col) = layout.decode(&code)?;
         Ok (Seat{
-            code: code,
-            row: row_min,
-            col: col_min,
-            id: row_min*8+col_min
+            code,
+            row,
+            col,
+            id: layout.id(row, col)
         })
     }
 }
@@ -76,27 +69,141 @@ fn read_lines (filename: &Path) -> io::Result<io::Lines<io::BufReader<File>>> {
     Ok(io::BufReader::new(file).lines())
 }
 
+/// Command line: `day5 [--rows FIRST..END] [--map] [--ppm [FILE]] [--allocate N [POLICY]]`
+/// and the layout options. --row-bits N, --col-bits N, --row-letters XY and --col-letters XY
+/// change the layout from the default 7 row bits picked with F/B and 3 column bits picked
+/// with L/R.
+struct Options {
+    layout: Layout,
+    /// The rows that exist on the aircraft, every row of the layout if not given.
+    rows: Option<Range<u32>>,
+    map: bool,
+    ppm: Option<String>,
+    allocate: Option<(usize, Policy)>
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Options, String> {
+        let (mut row_bits, mut col_bits, mut row_letters, mut col_letters) = (7, 3, ('F', 'B'), ('L', 'R'));
+        let mut options = Options { layout: Layout::default(), rows: None, map: false, ppm: None, allocate: None };
+        let mut args = args.iter().peekable();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+            let bits = |n: &String| n.parse().map_err(|_| format!("Bad bit count '{}' for {}", n, arg));
+            match arg.as_str() {
+                "--row-bits" => row_bits = bits(value()?)?,
+                "--col-bits" => col_bits = bits(value()?)?,
+                "--row-letters" => row_letters = parse_letters(value()?)?,
+                "--col-letters" => col_letters = parse_letters(value()?)?,
+                "--rows" => options.rows = Some(parse_rows(value()?)?),
+                "--map" => options.map = true,
+                // The file name and the policy are optional, so a following option is not taken as one.
+                "--ppm" => {
+                    let file = args.next_if(|file| !file.starts_with("--"));
+                    options.ppm = Some(file.map(|f| f.as_str()).unwrap_or("seats.ppm").to_string());
+                },
+                "--allocate" => {
+                    let count = value()?;
+                    let passengers = count.parse().map_err(|_| format!("Bad passenger count '{}' for --allocate", count))?;
+                    let policy = match args.next_if(|policy| !policy.starts_with("--")) {
+                        Some(policy) => policy.parse()?,
+                        None => Policy::FirstFree
+                    };
+                    options.allocate = Some((passengers, policy));
+                },
+                _ if arg.starts_with("--") => return Err(format!("Unknown option {}", arg)),
+                _ => return Err(format!("Unexpected argument '{}'", arg))
+            }
+        }
+        options.layout = Layout::new(row_bits, col_bits, row_letters, col_letters)
+            .map_err(|e| format!("Bad layout: {}", e))?;
+        Ok(options)
+    }
+}
+
 fn main() -> Result<(), Error>{
     let filename = Path::new("input.txt");
     let lines = read_lines(filename)?;
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = Options::parse(&args).map_err(|e| Error::new(io::ErrorKind::InvalidInput, e))?;
+    let layout = options.layout.clone();
+    let valid_rows = options.rows.clone().unwrap_or(0..layout.rows());
 
-    let mut seats: Vec<Seat> =  vec![];
+    let mut seats: Vec<(usize, Seat)> =  vec![];
 
-    for line in lines {
-        if let Ok(ip) = line {
-            if let Ok(seat) = Seat::new(ip) {
-                seats.push(seat);
-            }
+    let mut bad_passes: Vec<(usize, String, String)> = vec![];
+
+    for (i, line) in lines.enumerate() {
+        // A line that is not valid UTF-8 is a bad pass, the lines after it are still read.
+        let ip = match line {
+            Ok(ip) => ip,
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                bad_passes.push((i + 1, String::new(), "is not valid UTF-8".to_string()));
+                continue;
+            },
+            Err(e) => return Err(e)
+        };
+        match Seat::with_layout(ip.clone(), &layout) {
+            Ok(seat) => seats.push((i + 1, seat)),
+            Err(e) => bad_passes.push((i + 1, ip, e.to_string()))
+        }
+    }
+
+    // Duplicate passes and seats in rows that do not exist are left off the map.
+    let problems = check_seats(&seats, &valid_rows);
+    let mut problem_lines = HashSet::new();
+    for (line, problem) in problems {
+        problem_lines.insert(line);
+        let code = seats.iter().find(|(l, _)| *l == line).map(|(_, seat)| seat.code.clone()).unwrap_or_default();
+        bad_passes.push((line, code, problem.to_string()));
+    }
+    bad_passes.sort_by_key(|(line, _, _)| *line);
+    let occupied = seats.iter()
+        .filter(|(line, _)| !problem_lines.contains(line))
+        .map(|(_, seat)| seat.id);
+    let seat_map = SeatMap::new(&layout, occupied);
+
+    // --map prints the seat map, --ppm FILE writes it as an image.
+    if options.map {
+        println!("{}", seat_map.render()?);
+    }
+    if let Some(image_file) = &options.ppm {
+        seat_map.write_ppm(File::create(image_file)?, 8)?;
+        println!("Wrote seat map to {}", image_file);
+    }
+
+    for gap in seat_map.gaps() {
+        println!("Empty seat IDs: {}..={}", gap.start, gap.end - 1);
+    }
+    for (row, runs) in seat_map.empty_runs() {
+        let cols: Vec<String> = runs.iter().map(|run| format!("{}..={}", run.start, run.end - 1)).collect();
+        println!("Row {} empty columns: {}", row, cols.join(", "));
+    }
+    for id in seat_map.enclosed() {
+        if let Some((row, col)) = layout.seat_of(id) {
+            println!("Free seat ID: {} Code: {} (both neighbours taken)", id, layout.encode(row, col));
         }
     }
 
-    seats.sort();
-    let mut last_id = 0;
-    for seat in seats {
-        if seat.id - last_id != 1 {
-            println!("{}", seat);
+    // --allocate N [first|window|together] issues boarding passes for new passengers.
+    if let Some((passengers, policy)) = options.allocate {
+        let mut allocator = Allocator::new(&layout, &seat_map);
+        match allocator.assign(passengers, policy) {
+            Ok(issued) => {
+                for seat in issued {
+                    println!("Issued {}", seat);
+                }
+                println!("{} seats left", allocator.free());
+            },
+            Err(e) => println!("Could not allocate: {}", e)
+        }
+    }
+
+    if !bad_passes.is_empty() {
+        println!("{} bad boarding passes:", bad_passes.len());
+        for (line, code, e) in bad_passes {
+            println!("  line {}: '{}' {}", line, code, e);
         }
-        last_id = seat.id;
     }
 
     //let max_value = match seats.iter().max() {
@@ -114,6 +221,47 @@ mod tests {
 
     use super::*;
 
+    fn args(args: &str) -> Vec<String> {
+        args.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn layout_options() {
+        let layout = |text: &str| Options::parse(&args(text)).map(|options| options.layout);
+        assert_eq!(layout("--rows 1..5"), Ok(Layout::default()));
+        let small = layout("--row-bits 3 --col-bits 2 --row-letters UD --col-letters <>").unwrap();
+        assert_eq!(small, Layout::new(3, 2, ('U', 'D'), ('<', '>')).unwrap());
+        assert_eq!(layout("--row-bits 20 --col-bits 12").unwrap_err(),
+            "Bad layout: 32 bits is too many, at most 31 fit");
+        assert!(layout("--row-bits x").is_err());
+        assert!(layout("--col-bits").is_err());
+        assert!(layout("--row-letters FF").is_err());
+    }
+
+    #[test]
+    fn options() {
+        let options = Options::parse(&args("--allocate 3 --map --ppm --rows 1..=5")).unwrap();
+        assert_eq!(options.allocate, Some((3, Policy::FirstFree)));
+        assert!(options.map);
+        assert_eq!(options.ppm.as_deref(), Some("seats.ppm"));
+        assert_eq!(options.rows, Some(1..6));
+        let options = Options::parse(&args("--ppm out.ppm --allocate 2 window")).unwrap();
+        assert_eq!(options.ppm.as_deref(), Some("out.ppm"));
+        assert_eq!(options.allocate, Some((2, Policy::WindowFirst)));
+
+        assert!(Options::parse(&args("--allocate window")).is_err());
+        assert!(Options::parse(&args("--allocate 2 aisle")).is_err());
+        assert!(Options::parse(&args("--rows 5")).is_err());
+        assert_eq!(Options::parse(&args("--mpa")).err().unwrap(), "Unknown option --mpa");
+        assert!(Options::parse(&args("map")).is_err());
+    }
+
+    #[test]
+    fn case_bad() {
+        assert_eq!(Seat::new("FBFBBFFRL".to_string()).err(), Some(CodeError::Length { expected: 10, found: 9 }));
+        assert!(Seat::new("FBFBBFFRLRL".to_string()).is_err());
+    }
+
     #[test]
     fn case_good1() {
         let test = "FBFBBFFRLR";
@@ -122,7 +270,7 @@ mod tests {
             assert_eq!(seat.col, 5);
             assert_eq!(seat.id, 357);
         } else {
-            assert! (false);
+            panic!("Seat should decode.");
         }
     }
 }
\ No newline at end of file
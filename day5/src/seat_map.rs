@@ -0,0 +1,199 @@
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+use std::ops::Range;
+
+use crate::layout::Layout;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SeatState {
+    /// A boarding pass was seen for the seat.
+    Occupied,
+    /// No pass, but the seat lies between occupied seats.
+    Empty,
+    /// No pass and before the first or after the last occupied seat, so the seat is taken
+    /// not to exist on this aircraft.
+    Missing
+}
+
+/// The most seats `render` and `write_ppm` will draw, 64 times the puzzle's aircraft.
+pub const MAX_DRAWN_SEATS: u32 = 1 << 16;
+
+/// Every seat of a layout, marked from the ids of the boarding passes seen. Only the
+/// occupied ids are stored, so the size of the layout does not matter until it is drawn.
+pub struct SeatMap {
+    layout: Layout,
+    occupied: BTreeSet<u32>
+}
+
+impl SeatMap {
+    /// Ids outside the layout are ignored.
+    pub fn new(layout: &Layout, ids: impl Iterator<Item = u32>) -> SeatMap {
+        let occupied = ids.filter(|id| layout.seat_of(*id).is_some()).collect();
+        SeatMap { layout: layout.clone(), occupied }
+    }
+
+    pub fn state(&self, id: u32) -> SeatState {
+        if self.occupied.contains(&id) {
+            return SeatState::Occupied;
+        }
+        match (self.occupied.iter().next(), self.occupied.iter().next_back()) {
+            (Some(first), Some(last)) if id > *first && id < *last => SeatState::Empty,
+            _ => SeatState::Missing
+        }
+    }
+
+    /// Runs of empty seat ids, in order.
+    pub fn gaps(&self) -> Vec<Range<u32>> {
+        self.occupied.iter()
+            .zip(self.occupied.iter().skip(1))
+            .filter(|(id, next)| **next > **id + 1)
+            .map(|(id, next)| id + 1..*next)
+            .collect()
+    }
+
+    /// For every row with empty seats, the runs of empty columns.
+    pub fn empty_runs(&self) -> Vec<(u32, Vec<Range<u32>>)> {
+        let cols = self.layout.cols();
+        let mut rows: Vec<(u32, Vec<Range<u32>>)> = vec![];
+        for gap in self.gaps() {
+            // A gap can run over the end of a row into the next ones.
+            let mut start = gap.start;
+            while start < gap.end {
+                let (row, col) = self.layout.seat_of(start).expect("gaps are between seats on the aircraft");
+                let end = gap.end.min(start - col + cols);
+                let run = col..col + (end - start);
+                match rows.last_mut() {
+                    Some((last_row, runs)) if *last_row == row => runs.push(run),
+                    _ => rows.push((row, vec![run]))
+                }
+                start = end;
+            }
+        }
+        rows
+    }
+
+    /// Empty seats where the ids on both sides are occupied, like the seat the puzzle is after.
+    pub fn enclosed(&self) -> Vec<u32> {
+        self.gaps().into_iter().filter(|gap| gap.len() == 1).map(|gap| gap.start).collect()
+    }
+
+    /// Fails for layouts with more than `MAX_DRAWN_SEATS` seats.
+    fn check_drawable(&self) -> io::Result<()> {
+        let seats = u64::from(self.layout.rows()) * u64::from(self.layout.cols());
+        if seats > u64::from(MAX_DRAWN_SEATS) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                format!("the layout has {} seats, at most {} can be drawn", seats, MAX_DRAWN_SEATS)));
+        }
+        Ok(())
+    }
+
+    /// One line per row: `#` occupied, `.` empty and a space for missing seats.
+    pub fn render(&self) -> io::Result<String> {
+        self.check_drawable()?;
+        let mut lines = vec![];
+        for row in 0..self.layout.rows() {
+            let seats: String = (0..self.layout.cols())
+                .map(|col| match self.state(self.layout.id(row, col)) {
+                    SeatState::Occupied => '#',
+                    SeatState::Empty => '.',
+                    SeatState::Missing => ' '
+                })
+                .collect();
+            lines.push(format!("{:>4} {}", row, seats).trim_end().to_string());
+        }
+        Ok(lines.join("\n"))
+    }
+
+    /// Writes the map as a binary PPM image, each seat a `scale` pixel square with a one
+    /// pixel border. Occupied seats are blue, empty seats red and missing seats grey.
+    pub fn write_ppm<W: Write>(&self, mut writer: W, scale: usize) -> io::Result<()> {
+        self.check_drawable()?;
+        let cell = scale + 1;
+        let width = self.layout.cols() as usize * cell + 1;
+        let height = self.layout.rows() as usize * cell + 1;
+        write!(writer, "P6\n{} {}\n255\n", width, height)?;
+
+        let mut pixels = Vec::with_capacity(width * height * 3);
+        for y in 0..height {
+            for x in 0..width {
+                let colour: [u8; 3] = if x % cell == 0 || y % cell == 0 {
+                    [255, 255, 255]
+                } else {
+                    let id = self.layout.id((y / cell) as u32, (x / cell) as u32);
+                    match self.state(id) {
+                        SeatState::Occupied => [40, 90, 200],
+                        SeatState::Empty => [220, 40, 40],
+                        SeatState::Missing => [200, 200, 200]
+                    }
+                };
+                pixels.extend_from_slice(&colour);
+            }
+        }
+        writer.write_all(&pixels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn small() -> Layout {
+        Layout::new(2, 2, ('F', 'B'), ('L', 'R')).unwrap()
+    }
+
+    #[test]
+    fn states_and_analysis() {
+        // Seats 0, 1 and 15 do not exist, 5, 8, 9 and 10 are empty.
+        let map = SeatMap::new(&small(), [2, 3, 4, 6, 7, 11, 12, 13, 14].iter().copied());
+        assert_eq!(map.state(0), SeatState::Missing);
+        assert_eq!(map.state(2), SeatState::Occupied);
+        assert_eq!(map.state(5), SeatState::Empty);
+        assert_eq!(map.state(15), SeatState::Missing);
+        assert_eq!(map.state(99), SeatState::Missing);
+
+        assert_eq!(map.gaps(), vec![5..6, 8..11]);
+        assert_eq!(format!("{:?}", map.empty_runs()), "[(1, [1..2]), (2, [0..3])]");
+        assert_eq!(map.enclosed(), vec![5]);
+        assert_eq!(map.render().unwrap(), "   0   ##\n   1 #.##\n   2 ...#\n   3 ###");
+    }
+
+    #[test]
+    fn empty_map() {
+        let map = SeatMap::new(&small(), [].iter().copied());
+        assert!(map.gaps().is_empty());
+        assert!(map.enclosed().is_empty());
+        assert_eq!(map.state(3), SeatState::Missing);
+    }
+
+    #[test]
+    fn ppm_size() {
+        let map = SeatMap::new(&small(), [2, 3].iter().copied());
+        let mut image = vec![];
+        map.write_ppm(&mut image, 3).unwrap();
+        let header = b"P6\n17 17\n255\n";
+        assert!(image.starts_with(header));
+        assert_eq!(image.len(), header.len() + 17 * 17 * 3);
+    }
+
+    #[test]
+    fn large_layouts() {
+        // 2^31 seats, which only cost memory for the occupied ones.
+        let layout = Layout::new(16, 15, ('F', 'B'), ('L', 'R')).unwrap();
+        let last_row = layout.id(65535, 0);
+        let map = SeatMap::new(&layout, vec![1, 3, 32766, last_row + 2, u32::MAX].into_iter());
+        assert_eq!(map.state(u32::MAX), SeatState::Missing);
+        assert_eq!(map.state(last_row), SeatState::Empty);
+        assert_eq!(map.enclosed(), vec![2]);
+        assert_eq!(map.gaps(), vec![2..3, 4..32766, 32767..last_row + 2]);
+        // The last gap runs from the end of row 0 through every row to the start of the last.
+        let runs = map.empty_runs();
+        assert_eq!(runs.len(), 65536);
+        assert_eq!(runs[0], (0, vec![2..3, 4..32766, 32767..32768]));
+        assert_eq!(format!("{:?}", runs[1]), "(1, [0..32768])");
+        assert_eq!(format!("{:?}", runs[65535]), "(65535, [0..2])");
+
+        assert!(map.render().is_err());
+        assert!(map.write_ppm(vec![], 8).is_err());
+    }
+}
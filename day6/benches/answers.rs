@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use day6::{GroupAnswers, Operation};
+
+const GROUPS: usize = 1_000_000;
+
+/// Groups of one to five people, each answering a random handful of `a` to `z`.
+fn generate_groups() -> Vec<Vec<String>> {
+    let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed
+    };
+    (0..GROUPS)
+        .map(|_| {
+            (0..next() % 5 + 1)
+                .map(|_| {
+                    let bits = next() & next() & 0x3ff_ffff;
+                    (0..26u8).filter(|bit| bits >> bit & 1 == 1).map(|bit| (b'a' + bit) as char).collect()
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// The `HashMap<char, u32>` counter day6 used before it kept per person answers.
+fn map_counts(lines: &[String]) -> (usize, usize) {
+    let mut map: HashMap<char, u32> = HashMap::new();
+    for c in lines.iter().flat_map(|line| line.chars()) {
+        *map.entry(c).or_insert(0) += 1;
+    }
+    let all = map.values().filter(|count| **count == lines.len() as u32).count();
+    (map.len(), all)
+}
+
+fn answers(c: &mut Criterion) {
+    let groups = generate_groups();
+    let mut group = c.benchmark_group("union_and_all");
+    group.sample_size(10);
+
+    group.bench_function("map", |b| b.iter(|| {
+        groups.iter().map(|lines| map_counts(lines)).fold((0, 0), |sum, n| (sum.0 + n.0, sum.1 + n.1))
+    }));
+    group.bench_function("sets", |b| b.iter(|| {
+        groups.iter()
+            .map(|lines| GroupAnswers::with_sets(lines))
+            .fold((0, 0), |sum, g| (sum.0 + g.count(Operation::Union), sum.1 + g.count(Operation::Intersection)))
+    }));
+    group.bench_function("bits", |b| b.iter(|| {
+        groups.iter()
+            .map(|lines| GroupAnswers::new(lines))
+            .fold((0, 0), |sum, g| (sum.0 + g.count(Operation::Union), sum.1 + g.count(Operation::Intersection)))
+    }));
+    group.finish();
+
+    let parsed: Vec<GroupAnswers> = groups.iter().map(|lines| GroupAnswers::new(lines)).collect();
+    c.bench_function("bits_at_least_2_parsed", |b| b.iter(|| {
+        parsed.iter().map(|g| g.count(black_box(Operation::AtLeast(2)))).sum::<usize>()
+    }));
+}
+
+criterion_group!(benches, answers);
+criterion_main!(benches);
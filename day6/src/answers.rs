@@ -0,0 +1,352 @@
+use std::cmp::{Ordering, PartialEq};
+use std::collections::BTreeSet;
+use std::hash::{Hash, Hasher};
+
+/// Ways of combining the answers of the people in a group.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operation {
+    /// Questions anyone answered.
+    Union,
+    /// Questions everyone answered.
+    Intersection,
+    /// Questions an odd number of people answered, the symmetric difference of every set.
+    SymmetricDifference,
+    /// Questions at least this many people answered.
+    AtLeast(usize),
+    /// Questions exactly one person answered.
+    ExactlyOne
+}
+
+impl std::str::FromStr for Operation {
+    type Err = String;
+
+    /// Names are `union`, `all`, `odd`, `one` or `atleast:K`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "union" | "any" => Ok(Operation::Union),
+            "intersection" | "all" => Ok(Operation::Intersection),
+            "odd" | "xor" => Ok(Operation::SymmetricDifference),
+            "one" => Ok(Operation::ExactlyOne),
+            _ => s.strip_prefix("atleast:")
+                .and_then(|k| k.parse().ok())
+                .map(Operation::AtLeast)
+                .ok_or_else(|| format!("Unknown operation '{}', expected union, all, odd, one or atleast:K", s))
+        }
+    }
+}
+
+impl std::fmt::Display for Operation {
+    fn fmt (&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operation::Union => write!(f, "union"),
+            Operation::Intersection => write!(f, "all"),
+            Operation::SymmetricDifference => write!(f, "odd"),
+            Operation::AtLeast(k) => write!(f, "atleast:{}", k),
+            Operation::ExactlyOne => write!(f, "one")
+        }
+    }
+}
+
+/// The questions each person in a group answered yes to. Groups that only use the
+/// questions `a` to `z` keep one bitmask per person, anything else falls back to sets.
+#[derive(Debug)]
+pub struct GroupAnswers {
+    people: People
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum People {
+    /// Bit 0 is question `a`, bit 25 question `z`.
+    Bits(Vec<u32>),
+    Sets(Vec<BTreeSet<char>>)
+}
+
+const QUESTIONS: u32 = 26;
+
+fn bits_of(line: &str) -> Option<u32> {
+    line.chars()
+        .filter(|c| !c.is_whitespace())
+        .try_fold(0, |bits, c| if c.is_ascii_lowercase() { Some(bits | 1 << (c as u32 - 'a' as u32)) } else { None })
+}
+
+fn chars_of(bits: u32) -> BTreeSet<char> {
+    (0..QUESTIONS).filter(|bit| bits >> bit & 1 == 1).map(|bit| (b'a' + bit as u8) as char).collect()
+}
+
+fn bits_answers(people: &[u32], operation: Operation) -> u32 {
+    match operation {
+        Operation::Union => people.iter().fold(0, |any, person| any | person),
+        Operation::Intersection if people.is_empty() => 0,
+        Operation::Intersection => people.iter().fold(u32::MAX, |all, person| all & person),
+        Operation::SymmetricDifference => people.iter().fold(0, |odd, person| odd ^ person),
+        Operation::AtLeast(k) if k <= 1 => bits_answers(people, Operation::Union),
+        Operation::AtLeast(2) => once_and_more(people).1,
+        Operation::AtLeast(k) => {
+            let any = bits_answers(people, Operation::Union);
+            (0..QUESTIONS)
+                .filter(|bit| any >> bit & 1 == 1)
+                .filter(|bit| people.iter().filter(|person| *person >> bit & 1 == 1).count() >= k)
+                .fold(0, |bits, bit| bits | 1 << bit)
+        },
+        Operation::ExactlyOne => once_and_more(people).0
+    }
+}
+
+/// The questions answered by exactly one person, and by more than one.
+fn once_and_more(people: &[u32]) -> (u32, u32) {
+    let mut once = 0;
+    let mut more = 0;
+    for person in people {
+        more |= once & person;
+        once = (once | person) & !more;
+    }
+    (once, more)
+}
+
+fn set_answers(people: &[BTreeSet<char>], operation: Operation) -> BTreeSet<char> {
+    let by_count = |keep: &dyn Fn(usize) -> bool| set_answers(people, Operation::Union)
+        .into_iter()
+        .filter(|question| keep(people.iter().filter(|person| person.contains(question)).count()))
+        .collect();
+    match operation {
+        Operation::Union => people.iter().flatten().copied().collect(),
+        Operation::Intersection => match people.split_first() {
+            Some((first, rest)) => rest.iter()
+                .fold(first.clone(), |all, person| all.intersection(person).copied().collect()),
+            None => BTreeSet::new()
+        },
+        Operation::SymmetricDifference => people.iter()
+            .fold(BTreeSet::new(), |odd, person| odd.symmetric_difference(person).copied().collect()),
+        Operation::AtLeast(k) => by_count(&|count| count >= k),
+        Operation::ExactlyOne => by_count(&|count| count == 1)
+    }
+}
+
+impl GroupAnswers {
+    /// One line of answers per person, spaces are ignored.
+    pub fn new<S: AsRef<str>>(lines: &[S]) -> GroupAnswers {
+        match lines.iter().map(|line| bits_of(line.as_ref())).collect() {
+            Some(bits) => GroupAnswers { people: People::Bits(bits) },
+            None => GroupAnswers::with_sets(lines)
+        }
+    }
+
+    /// Always uses the set representation, whatever the questions are.
+    pub fn with_sets<S: AsRef<str>>(lines: &[S]) -> GroupAnswers {
+        let people = lines.iter()
+            .map(|line| line.as_ref().chars().filter(|c| !c.is_whitespace()).collect())
+            .collect();
+        GroupAnswers { people: People::Sets(people) }
+    }
+
+    pub fn people(&self) -> usize {
+        match &self.people {
+            People::Bits(people) => people.len(),
+            People::Sets(people) => people.len()
+        }
+    }
+
+    /// The questions picked out by `operation`.
+    pub fn answers(&self, operation: Operation) -> BTreeSet<char> {
+        match &self.people {
+            People::Bits(people) => chars_of(bits_answers(people, operation)),
+            People::Sets(people) => set_answers(people, operation)
+        }
+    }
+
+    /// What each person answered, in the order they were given.
+    pub fn each_person(&self) -> Vec<BTreeSet<char>> {
+        match &self.people {
+            People::Bits(people) => people.iter().map(|person| chars_of(*person)).collect(),
+            People::Sets(people) => people.clone()
+        }
+    }
+
+    /// Each person's answers in sorted order, so the order people are listed in and the
+    /// representation do not matter.
+    fn canonical(&self) -> Vec<BTreeSet<char>> {
+        let mut people = self.each_person();
+        people.sort();
+        people
+    }
+
+    pub fn count(&self, operation: Operation) -> usize {
+        match &self.people {
+            People::Bits(people) => bits_answers(people, operation).count_ones() as usize,
+            People::Sets(people) => set_answers(people, operation).len()
+        }
+    }
+}
+
+/// Groups are equal when the same answers were given, whoever in the group gave them.
+impl PartialEq for GroupAnswers {
+    fn eq(&self, other: &Self) -> bool {
+        self.canonical() == other.canonical()
+    }
+}
+
+impl Eq for GroupAnswers {}
+
+impl Hash for GroupAnswers {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.canonical().hash(state)
+    }
+}
+
+/// Orders by answers, which keeps equal groups together for `dedup`. Use `SortKey` to rank.
+impl PartialOrd for GroupAnswers {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for GroupAnswers {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.canonical().cmp(&other.canonical())
+    }
+}
+
+/// Ways of ranking groups.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortKey {
+    /// Questions anyone in the group answered.
+    Union,
+    /// Questions everyone in the group answered.
+    Agreement,
+    /// People in the group.
+    Size
+}
+
+impl SortKey {
+    pub fn key(&self, group: &GroupAnswers) -> usize {
+        match self {
+            SortKey::Union => group.count(Operation::Union),
+            SortKey::Agreement => group.count(Operation::Intersection),
+            SortKey::Size => group.people()
+        }
+    }
+}
+
+impl std::str::FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "union" => Ok(SortKey::Union),
+            "agreement" | "all" => Ok(SortKey::Agreement),
+            "size" => Ok(SortKey::Size),
+            _ => Err(format!("Unknown sort key '{}', expected union, agreement or size", s))
+        }
+    }
+}
+
+/// Each person's answers, separated by spaces.
+impl std::fmt::Display for GroupAnswers {
+    fn fmt (&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let people: Vec<String> = self.each_person().into_iter().map(|person| person.into_iter().collect()).collect();
+        write!(f, "{}", people.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn text(set: BTreeSet<char>) -> String {
+        set.into_iter().collect()
+    }
+
+    const OPERATIONS: [Operation; 7] = [
+        Operation::Union,
+        Operation::Intersection,
+        Operation::SymmetricDifference,
+        Operation::AtLeast(0),
+        Operation::AtLeast(2),
+        Operation::AtLeast(3),
+        Operation::ExactlyOne
+    ];
+
+    #[test]
+    fn operations() {
+        for group in [GroupAnswers::new(&["abc", "ab d", "ae"]), GroupAnswers::with_sets(&["abc", "ab d", "ae"])].iter() {
+            assert_eq!(group.people(), 3);
+            assert_eq!(text(group.answers(Operation::Union)), "abcde");
+            assert_eq!(text(group.answers(Operation::Intersection)), "a");
+            assert_eq!(text(group.answers(Operation::SymmetricDifference)), "acde");
+            assert_eq!(text(group.answers(Operation::AtLeast(2))), "ab");
+            assert_eq!(text(group.answers(Operation::ExactlyOne)), "cde");
+            assert_eq!(group.count(Operation::AtLeast(4)), 0);
+        }
+    }
+
+    #[test]
+    fn bits_match_sets() {
+        let groups: [&[&str]; 5] = [&[], &["z"], &["abz", "bz", "zyx", "a"], &["qwerty", "ytrewq"], &["abc", "", "cba"]];
+        for lines in groups.iter() {
+            let bits = GroupAnswers::new(lines);
+            assert!(matches!(bits.people, People::Bits(_)));
+            let sets = GroupAnswers::with_sets(lines);
+            for operation in OPERATIONS.iter() {
+                assert_eq!(bits.answers(*operation), sets.answers(*operation), "{:?} {}", lines, operation);
+                assert_eq!(bits.count(*operation), sets.count(*operation));
+            }
+        }
+    }
+
+    #[test]
+    fn other_alphabets() {
+        let group = GroupAnswers::new(&["aB1", "B1é", "é"]);
+        assert!(matches!(group.people, People::Sets(_)));
+        assert_eq!(text(group.answers(Operation::AtLeast(2))), "1Bé");
+        assert_eq!(text(group.answers(Operation::ExactlyOne)), "a");
+    }
+
+    #[test]
+    fn puzzle_example() {
+        let groups = ["abc", "a\nb\nc", "ab\nac", "a\na\na\na", "b"];
+        let sum = |operation| groups.iter()
+            .map(|g| GroupAnswers::new(&g.lines().collect::<Vec<_>>()).count(operation))
+            .sum::<usize>();
+        assert_eq!(sum(Operation::Union), 11);
+        assert_eq!(sum(Operation::Intersection), 6);
+    }
+
+    #[test]
+    fn names() {
+        for name in ["union", "all", "odd", "one", "atleast:3"].iter() {
+            let operation: Operation = name.parse().unwrap();
+            assert_eq!(operation.to_string(), *name);
+        }
+        assert!("atleast:x".parse::<Operation>().is_err());
+        assert!(GroupAnswers::new::<&str>(&[]).answers(Operation::Intersection).is_empty());
+    }
+
+    #[test]
+    fn equality_by_contents() {
+        // Same union size, different answers.
+        assert_ne!(GroupAnswers::new(&["ab"]), GroupAnswers::new(&["cd"]));
+        assert_eq!(GroupAnswers::new(&["ab", "c"]), GroupAnswers::new(&["c", "b a"]));
+        assert_eq!(GroupAnswers::new(&["ab"]), GroupAnswers::with_sets(&["ab"]));
+        assert_ne!(GroupAnswers::new(&["ab"]), GroupAnswers::new(&["ab", "ab"]));
+
+        let unique: std::collections::HashSet<GroupAnswers> =
+            vec![GroupAnswers::new(&["ab"]), GroupAnswers::with_sets(&["ba"]), GroupAnswers::new(&["cd"])].into_iter().collect();
+        assert_eq!(unique.len(), 2);
+
+        let mut groups = vec![GroupAnswers::new(&["cd"]), GroupAnswers::new(&["ab"]), GroupAnswers::new(&["dc"])];
+        groups.sort();
+        groups.dedup();
+        assert_eq!(groups.iter().map(|g| g.to_string()).collect::<Vec<_>>(), ["ab", "cd"]);
+    }
+
+    #[test]
+    fn sort_keys() {
+        let group = GroupAnswers::new(&["abc", "ab", "bd"]);
+        assert_eq!(SortKey::Union.key(&group), 4);
+        assert_eq!(SortKey::Agreement.key(&group), 1);
+        assert_eq!(SortKey::Size.key(&group), 3);
+        assert_eq!("all".parse(), Ok(SortKey::Agreement));
+        assert!("people".parse::<SortKey>().is_err());
+    }
+}
@@ -0,0 +1,5 @@
+mod answers;
+mod report;
+
+pub use answers::{GroupAnswers, Operation, SortKey};
+pub use report::Report;
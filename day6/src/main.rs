@@ -1,98 +1,143 @@
 use std::fs::File;
-use std::io::{self, BufRead, Error};
+use std::io::{self, Error};
 use std::path::Path;
-use std::collections::HashMap;
-use std::cmp::{PartialEq, Ordering};
 
-#[derive(Eq)]
-struct GroupAnswers {
-    map: HashMap<char, u32>,
-    people: u32
+use records::Records;
+
+use day6::{GroupAnswers, Operation, Report, SortKey};
+
+/// How `--report` prints the survey report.
+#[derive(Debug, PartialEq)]
+enum ReportFormat {
+    Text,
+    Csv
 }
 
-impl GroupAnswers {
-    fn new(input: String, people: u32) -> GroupAnswers {
-        let mut map = GroupAnswers { map: HashMap::new(), people};
+impl std::str::FromStr for ReportFormat {
+    type Err = String;
 
-        for a_char in input.chars() {
-            if a_char != ' ' {
-                let entry = map.map.entry(a_char).or_insert(0);
-                *entry += 1;
-            }
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(ReportFormat::Text),
+            "csv" => Ok(ReportFormat::Csv),
+            _ => Err(format!("Unknown report format '{}', expected text or csv", s))
         }
-
-        map
     }
+}
 
-    fn value(&self) -> usize {
-        self.map.len()
-    }
+struct Options {
+    operations: Vec<Operation>,
+    per_group: bool,
+    input: Option<String>,
+    report: Option<ReportFormat>,
+    top: Option<usize>,
+    by: SortKey
+}
 
-    fn matched_value(&self) -> usize {
-        let mut count = 0;
-        for (_, val) in self.map.iter() {
-            if *val == self.people {
-                count += 1;
+impl Options {
+    fn parse(args: &[String]) -> Result<Options, String> {
+        let mut options = Options { operations: vec![], per_group: false, input: None, report: None, top: None, by: SortKey::Union };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+            match arg.as_str() {
+                "--groups" => options.per_group = true,
+                "--input" => options.input = Some(value()?.clone()),
+                "--report" => options.report = Some(value()?.parse()?),
+                "--top" => {
+                    let top = value()?;
+                    options.top = Some(top.parse().map_err(|_| format!("Bad count '{}' for --top", top))?);
+                },
+                "--by" => options.by = value()?.parse()?,
+                _ if arg.starts_with("--") => return Err(format!("Unknown option {}", arg)),
+                _ => options.operations.push(arg.parse()?)
             }
         }
-        count
+        Ok(options)
     }
-
 }
 
-impl PartialEq for GroupAnswers {
-    fn eq(&self, other: &Self) -> bool {
-        self.value() == other.value()
-    }
-}
+/// Arguments are the operations to sum over every group, such as `union all odd one atleast:2`.
+/// `--groups` also prints the count of each operation for every group, and `--input FILE`
+/// reads another file than `input.txt`. `--report text|csv` prints the survey report instead,
+/// and `--top N --by union|agreement|size` lists the highest ranked groups.
+fn main() -> Result<(), Error> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = Options::parse(&args).map_err(|e| Error::new(io::ErrorKind::InvalidInput, e))?;
+    let operations = &options.operations;
 
-impl PartialOrd for GroupAnswers {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
-}
+    let filename = Path::new(options.input.as_deref().unwrap_or("input.txt"));
+    let mut groups: Vec<(usize, GroupAnswers)> = vec![];
 
-impl Ord for GroupAnswers {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.value().cmp(&other.value())
+    for record in Records::new(File::open(filename)?) {
+        let record = record?;
+        groups.push((record.line, GroupAnswers::new(&record.lines)));
     }
-}
 
-fn read_lines (filename: &Path) -> io::Result<io::Lines<io::BufReader<File>>> {
-    let file = File::open(filename)?;
-    Ok(io::BufReader::new(file).lines())
-}
+    if let Some(format) = options.report {
+        let report = Report::new(groups.iter().map(|(_, group)| group));
+        let stdout = io::stdout();
+        return match format {
+            ReportFormat::Text => report.write_text(stdout.lock(), 10),
+            ReportFormat::Csv => report.write_csv(stdout.lock())
+        };
+    }
 
-fn main() -> Result<(), Error> {
-    
-    let filename = Path::new("input.txt");
-    let lines = read_lines(filename)?;
-
-    let mut group_string = String::new();
-    let mut people_count = 0;
-    let mut groups: Vec<GroupAnswers> = vec![];
-
-    for line in lines {
-        if let Ok(ip) = line {
-            if ip != "" {
-                group_string.push_str(&ip);
-                people_count += 1;
-            } else {
-                groups.push(GroupAnswers::new(group_string, people_count));
-                group_string = String::new();
-                people_count = 0;
-            }
+    if let Some(top) = options.top {
+        let key = options.by;
+        let mut ranked: Vec<&(usize, GroupAnswers)> = groups.iter().collect();
+        ranked.sort_by(|(line_a, a), (line_b, b)| key.key(b).cmp(&key.key(a)).then(line_a.cmp(line_b)));
+        for (line, group) in ranked.into_iter().take(top) {
+            println!("{:>5} line {:>5}: {}", key.key(group), line, group);
         }
+        return Ok(());
     }
 
-    let mut sum = 0;
-    let mut matched_sum = 0;
-    for group in groups {
-        sum += group.value();
-        matched_sum += group.matched_value();
+    let sum = |operation| groups.iter().map(|(_, group)| group.count(operation)).sum::<usize>();
+
+    if operations.is_empty() {
+        println! ("Total: {}, Matched: {}", sum(Operation::Union), sum(Operation::Intersection));
+        return Ok(());
     }
 
-    println! ("Total: {}, Matched: {}", sum, matched_sum);
+    if options.per_group {
+        for (line, group) in &groups {
+            let counts: Vec<String> = operations.iter()
+                .map(|operation| format!("{}={}", operation, group.count(*operation)))
+                .collect();
+            println!("Group at line {} ({} people): {}", line, group.people(), counts.join(" "));
+        }
+    }
+    for operation in operations {
+        println!("{}: {}", operation, sum(*operation));
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn parse(args: &str) -> Result<Options, String> {
+        Options::parse(&args.split_whitespace().map(String::from).collect::<Vec<String>>())
+    }
+
+    #[test]
+    fn options() {
+        let options = parse("--input all all --by union union --top 3").unwrap();
+        assert_eq!(options.input.as_deref(), Some("all"));
+        assert_eq!(options.operations, [Operation::Intersection, Operation::Union]);
+        assert_eq!(options.by, SortKey::Union);
+        assert_eq!(options.top, Some(3));
+        assert_eq!(parse("--report csv").unwrap().report, Some(ReportFormat::Csv));
+
+        assert!(parse("--top x").is_err());
+        assert!(parse("--top").is_err());
+        assert!(parse("--report pdf").is_err());
+        assert!(parse("--by colour").is_err());
+        assert!(parse("--verbose").is_err());
+        assert!(parse("most").is_err());
+    }
+}
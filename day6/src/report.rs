@@ -0,0 +1,143 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use crate::answers::{GroupAnswers, Operation};
+
+/// How popular each question is over a set of groups.
+#[derive(Debug, Default, PartialEq)]
+pub struct Report {
+    pub groups: usize,
+    pub people: usize,
+    /// People who answered yes to each question.
+    pub yes: BTreeMap<char, usize>,
+    /// Groups where everyone answered yes to each question.
+    pub unanimous: BTreeMap<char, usize>,
+    /// Number of groups of each size.
+    pub group_sizes: BTreeMap<usize, usize>,
+    /// People who answered yes to both questions of a pair, the first question sorting first.
+    pub pairs: BTreeMap<(char, char), usize>
+}
+
+impl Report {
+    pub fn new<'a>(groups: impl IntoIterator<Item = &'a GroupAnswers>) -> Report {
+        let mut report = Report::default();
+        for group in groups {
+            report.groups += 1;
+            report.people += group.people();
+            *report.group_sizes.entry(group.people()).or_insert(0) += 1;
+            for question in group.answers(Operation::Intersection) {
+                *report.unanimous.entry(question).or_insert(0) += 1;
+            }
+            for person in group.each_person() {
+                for (i, question) in person.iter().enumerate() {
+                    *report.yes.entry(*question).or_insert(0) += 1;
+                    for other in person.iter().skip(i + 1) {
+                        *report.pairs.entry((*question, *other)).or_insert(0) += 1;
+                    }
+                }
+            }
+        }
+        report
+    }
+
+    /// Share of groups where everyone answered yes to `question`.
+    pub fn unanimous_share(&self, question: char) -> f64 {
+        if self.groups == 0 {
+            return 0.0;
+        }
+        self.unanimous.get(&question).copied().unwrap_or(0) as f64 / self.groups as f64
+    }
+
+    /// The `n` pairs answered together most often, ties in question order.
+    pub fn top_pairs(&self, n: usize) -> Vec<((char, char), usize)> {
+        let mut pairs: Vec<((char, char), usize)> = self.pairs.iter().map(|(pair, count)| (*pair, *count)).collect();
+        pairs.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        pairs.truncate(n);
+        pairs
+    }
+
+    /// Tables of the questions, group sizes and the `pairs` most common pairs.
+    pub fn write_text<W: Write>(&self, mut writer: W, pairs: usize) -> io::Result<()> {
+        writeln!(writer, "{} groups, {} people", self.groups, self.people)?;
+        writeln!(writer)?;
+        writeln!(writer, "question       yes  unanimous  share")?;
+        for (question, yes) in &self.yes {
+            let unanimous = self.unanimous.get(question).copied().unwrap_or(0);
+            writeln!(writer, "{:>8} {:>9} {:>10} {:>5.1}%", question, yes, unanimous, 100.0 * self.unanimous_share(*question))?;
+        }
+        writeln!(writer)?;
+        writeln!(writer, "group size    groups")?;
+        for (size, groups) in &self.group_sizes {
+            writeln!(writer, "{:>10} {:>9}", size, groups)?;
+        }
+        writeln!(writer)?;
+        writeln!(writer, "pair      people")?;
+        for ((first, second), count) in self.top_pairs(pairs) {
+            writeln!(writer, "{}{} {:>11}", first, second, count)?;
+        }
+        Ok(())
+    }
+
+    /// Every figure as a `section,key,count,share` row, so one file holds the whole report.
+    /// Shares are only filled in for the `unanimous` section.
+    pub fn write_csv<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut writer = csv::Writer::from_writer(writer);
+        writer.write_record(["section", "key", "count", "share"])?;
+        for (question, yes) in &self.yes {
+            writer.write_record(["yes", &question.to_string(), &yes.to_string(), ""])?;
+        }
+        for question in self.yes.keys() {
+            let unanimous = self.unanimous.get(question).copied().unwrap_or(0);
+            let share = format!("{:.4}", self.unanimous_share(*question));
+            writer.write_record(["unanimous", &question.to_string(), &unanimous.to_string(), &share])?;
+        }
+        for (size, groups) in &self.group_sizes {
+            writer.write_record(["group_size", &size.to_string(), &groups.to_string(), ""])?;
+        }
+        for ((first, second), count) in self.top_pairs(self.pairs.len()) {
+            writer.write_record(["pair", &format!("{}{}", first, second), &count.to_string(), ""])?;
+        }
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn report() -> Report {
+        let groups = [GroupAnswers::new(&["abc"]), GroupAnswers::new(&["ab", "ac"]), GroupAnswers::new(&["b", "b"])];
+        Report::new(groups.iter())
+    }
+
+    #[test]
+    fn figures() {
+        let report = report();
+        assert_eq!((report.groups, report.people), (3, 5));
+        assert_eq!(format!("{:?}", report.yes), "{'a': 3, 'b': 4, 'c': 2}");
+        assert_eq!(format!("{:?}", report.unanimous), "{'a': 2, 'b': 2, 'c': 1}");
+        assert_eq!(format!("{:?}", report.group_sizes), "{1: 1, 2: 2}");
+        assert_eq!(report.top_pairs(2), vec![(('a', 'b'), 2), (('a', 'c'), 2)]);
+        assert!((report.unanimous_share('c') - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(report.unanimous_share('z'), 0.0);
+    }
+
+    #[test]
+    fn csv() {
+        let mut out = vec![];
+        report().write_csv(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("section,key,count,share\nyes,a,3,\n"));
+        assert!(out.contains("\nunanimous,c,1,0.3333\n"));
+        assert!(out.contains("\ngroup_size,2,2,\n"));
+        assert!(out.ends_with("\npair,bc,1,\n"));
+    }
+
+    #[test]
+    fn empty() {
+        let mut out = vec![];
+        Report::new(&[]).write_text(&mut out, 5).unwrap();
+        assert!(String::from_utf8(out).unwrap().starts_with("0 groups, 0 people"));
+    }
+}
@@ -0,0 +1,151 @@
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+
+use crate::graph::{BagGraph, BagId};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// GraphViz DOT.
+    Dot,
+    Mermaid
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dot" | "gv" => Ok(Format::Dot),
+            "mermaid" | "mmd" => Ok(Format::Mermaid),
+            _ => Err(format!("Unknown format '{}', expected dot or mermaid", s))
+        }
+    }
+}
+
+/// Which part of the graph is exported.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scope {
+    All,
+    /// The bag and everything it eventually holds.
+    Contents(BagId),
+    /// The bag and every bag that can eventually hold it.
+    Containers(BagId)
+}
+
+impl Scope {
+    fn root(&self) -> Option<BagId> {
+        match self {
+            Scope::All => None,
+            Scope::Contents(root) | Scope::Containers(root) => Some(*root)
+        }
+    }
+}
+
+/// Writes the bags in `scope` and every rule between them, with the counts on the edges and
+/// the root of the scope highlighted.
+pub fn export<W: Write>(graph: &BagGraph, scope: Scope, format: Format, writer: W) -> io::Result<()> {
+    let bags: BTreeSet<BagId> = match scope {
+        Scope::All => (0..graph.len()).collect(),
+        Scope::Contents(root) => graph.descendants(root).into_iter().chain(Some(root)).collect(),
+        Scope::Containers(root) => graph.ancestors(root).into_iter().chain(Some(root)).collect()
+    };
+    let edges: Vec<(BagId, BagId, u32)> = bags.iter()
+        .flat_map(|outer| graph.contents(*outer).iter().map(move |(inner, count)| (*outer, *inner, *count)))
+        .filter(|(_, inner, _)| bags.contains(inner))
+        .collect();
+
+    match format {
+        Format::Dot => write_dot(graph, &bags, &edges, scope.root(), writer),
+        Format::Mermaid => write_mermaid(graph, &bags, &edges, scope.root(), writer)
+    }
+}
+
+fn quoted(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn write_dot<W: Write>(graph: &BagGraph, bags: &BTreeSet<BagId>, edges: &[(BagId, BagId, u32)], root: Option<BagId>, mut writer: W) -> io::Result<()> {
+    writeln!(writer, "digraph bags {{")?;
+    writeln!(writer, "    rankdir=LR;")?;
+    for bag in bags {
+        if Some(*bag) == root {
+            writeln!(writer, "    {} [style=filled, fillcolor=gold, penwidth=2];", quoted(graph.name(*bag)))?;
+        } else {
+            writeln!(writer, "    {};", quoted(graph.name(*bag)))?;
+        }
+    }
+    for (outer, inner, count) in edges {
+        writeln!(writer, "    {} -> {} [label=\"{}\"];", quoted(graph.name(*outer)), quoted(graph.name(*inner)), count)?;
+    }
+    writeln!(writer, "}}")
+}
+
+/// Nodes are named by bag id, as Mermaid ids cannot hold spaces.
+fn write_mermaid<W: Write>(graph: &BagGraph, bags: &BTreeSet<BagId>, edges: &[(BagId, BagId, u32)], root: Option<BagId>, mut writer: W) -> io::Result<()> {
+    writeln!(writer, "graph LR")?;
+    for bag in bags {
+        writeln!(writer, "    b{}[\"{}\"]", bag, graph.name(*bag).replace('"', "#quot;"))?;
+    }
+    for (outer, inner, count) in edges {
+        writeln!(writer, "    b{} -->|{}| b{}", outer, count, inner)?;
+    }
+    if let Some(root) = root {
+        writeln!(writer, "    style b{} fill:#fd0,stroke:#333,stroke-width:2px", root)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::to_hashmap;
+
+    const RULES: &str = "light red bags contain 1 shiny gold bag, 2 muted yellow bags.
+shiny gold bags contain 3 faded blue bags.
+muted yellow bags contain 4 faded blue bags.
+faded blue bags contain no other bags.";
+
+    fn exported(scope: impl Fn(&BagGraph) -> Scope, format: Format) -> String {
+        let graph = BagGraph::new(&to_hashmap(RULES).unwrap());
+        let mut out = vec![];
+        export(&graph, scope(&graph), format, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn dot_contents() {
+        let dot = exported(|graph| Scope::Contents(graph.id("shiny gold bag").unwrap()), Format::Dot);
+        assert_eq!(dot, "digraph bags {
+    rankdir=LR;
+    \"faded blue bag\";
+    \"shiny gold bag\" [style=filled, fillcolor=gold, penwidth=2];
+    \"shiny gold bag\" -> \"faded blue bag\" [label=\"3\"];
+}
+");
+    }
+
+    #[test]
+    fn mermaid_containers() {
+        let mermaid = exported(|graph| Scope::Containers(graph.id("faded blue bag").unwrap()), Format::Mermaid);
+        assert_eq!(mermaid, "graph LR
+    b0[\"faded blue bag\"]
+    b1[\"light red bag\"]
+    b2[\"muted yellow bag\"]
+    b3[\"shiny gold bag\"]
+    b1 -->|1| b3
+    b1 -->|2| b2
+    b2 -->|4| b0
+    b3 -->|3| b0
+    style b0 fill:#fd0,stroke:#333,stroke-width:2px
+");
+    }
+
+    #[test]
+    fn everything() {
+        let dot = exported(|_| Scope::All, Format::Dot);
+        assert_eq!(dot.matches(" -> ").count(), 4);
+        assert!(!dot.contains("fillcolor"));
+        assert_eq!("mmd".parse(), Ok(Format::Mermaid));
+    }
+}
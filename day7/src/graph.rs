@@ -0,0 +1,528 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+
+use num_bigint::BigUint;
+
+use crate::BagRule;
+
+/// Index of a bag colour in a `BagGraph`.
+pub type BagId = usize;
+
+/// The bag rules with every colour interned, so edges are plain indices both ways.
+/// `total_contents` is memoised across all bags, so totals for every bag cost one pass over
+/// the graph. `ancestors` is a search that is linear in the graph for each new target, and
+/// only repeat questions about the same target come from its cache.
+pub struct BagGraph {
+    names: Vec<String>,
+    ids: HashMap<String, BagId>,
+    /// Whether the bag has a rule of its own. Bags without one hold nothing.
+    defined: Vec<bool>,
+    /// (inner bag, count) for each bag.
+    contents: Vec<Vec<(BagId, u32)>>,
+    /// (outer bag, count) for each bag.
+    containers: Vec<Vec<(BagId, u32)>>,
+    /// Chains of bags that hold each other, each ending where it started.
+    cycles: Vec<Vec<BagId>>,
+    /// The cycle in `cycles` that each bag is on, if any.
+    cycle_of: Vec<Option<usize>>,
+    totals: RefCell<Vec<Option<u64>>>,
+    big_totals: RefCell<Vec<Option<BigUint>>>,
+    ancestors: RefCell<HashMap<BagId, Vec<BagId>>>
+}
+
+impl BagGraph {
+    pub fn new(rules: &HashMap<String, Vec<BagRule>>) -> BagGraph {
+        let mut graph = BagGraph {
+            names: vec![],
+            ids: HashMap::new(),
+            defined: vec![],
+            contents: vec![],
+            containers: vec![],
+            cycles: vec![],
+            cycle_of: vec![],
+            totals: RefCell::new(vec![]),
+            big_totals: RefCell::new(vec![]),
+            ancestors: RefCell::new(HashMap::new())
+        };
+
+        // Bags with rules get ids in name order, so ids do not depend on the hash map order.
+        let mut bags: Vec<&String> = rules.keys().collect();
+        bags.sort();
+        for bag in &bags {
+            let id = graph.intern(bag);
+            graph.defined[id] = true;
+        }
+        for bag in bags {
+            let outer = graph.ids[bag];
+            for rule in &rules[bag] {
+                let inner = graph.intern(&rule.bag_type);
+                graph.contents[outer].push((inner, rule.count));
+                graph.containers[inner].push((outer, rule.count));
+            }
+        }
+        *graph.totals.get_mut() = vec![None; graph.names.len()];
+        *graph.big_totals.get_mut() = vec![None; graph.names.len()];
+
+        graph.cycles = graph.find_cycles();
+        graph.cycle_of = vec![None; graph.names.len()];
+        for (i, cycle) in graph.cycles.iter().enumerate() {
+            for id in cycle {
+                graph.cycle_of[*id].get_or_insert(i);
+            }
+        }
+        graph
+    }
+
+    /// A depth first search that records the path back to an earlier bag every time one is
+    /// found. Every bag that can reach a cycle can reach one of the recorded chains.
+    fn find_cycles(&self) -> Vec<Vec<BagId>> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum Visit {
+            New,
+            OnPath,
+            Done
+        }
+
+        let mut visits = vec![Visit::New; self.len()];
+        let mut cycles = vec![];
+        for start in 0..self.len() {
+            if visits[start] != Visit::New {
+                continue;
+            }
+            // The path from `start`, with how many of each bag's contents have been looked at.
+            let mut path: Vec<(BagId, usize)> = vec![(start, 0)];
+            visits[start] = Visit::OnPath;
+            while let Some(&(id, next)) = path.last() {
+                let inner = match self.contents(id).get(next) {
+                    Some((inner, _)) => *inner,
+                    None => {
+                        visits[id] = Visit::Done;
+                        path.pop();
+                        continue;
+                    }
+                };
+                path.last_mut().unwrap().1 += 1;
+                match visits[inner] {
+                    Visit::New => {
+                        visits[inner] = Visit::OnPath;
+                        path.push((inner, 0));
+                    },
+                    Visit::OnPath => {
+                        let start = path.iter().rposition(|(bag, _)| *bag == inner).unwrap_or(0);
+                        let mut chain: Vec<BagId> = path[start..].iter().map(|(bag, _)| *bag).collect();
+                        chain.push(inner);
+                        cycles.push(chain);
+                    },
+                    Visit::Done => ()
+                }
+            }
+        }
+        cycles
+    }
+
+    fn intern(&mut self, name: &str) -> BagId {
+        if let Some(id) = self.ids.get(name) {
+            return *id;
+        }
+        let id = self.names.len();
+        self.names.push(name.to_string());
+        self.ids.insert(name.to_string(), id);
+        self.defined.push(false);
+        self.contents.push(vec![]);
+        self.containers.push(vec![]);
+        id
+    }
+
+    pub fn id(&self, name: &str) -> Option<BagId> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn name(&self, id: BagId) -> &str {
+        &self.names[id]
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// (inner bag, count) for everything `id` directly holds.
+    pub fn contents(&self, id: BagId) -> &[(BagId, u32)] {
+        &self.contents[id]
+    }
+
+    /// (outer bag, count) for every bag that directly holds `id`.
+    pub fn containers(&self, id: BagId) -> &[(BagId, u32)] {
+        &self.containers[id]
+    }
+
+    /// Every chain of bags that hold each other, by name, each ending where it started.
+    pub fn cycles(&self) -> Vec<Vec<&str>> {
+        self.cycles.iter().map(|cycle| cycle.iter().map(|id| self.name(*id)).collect()).collect()
+    }
+
+    /// Every (container, bag) where the container holds a bag that has no rule, by name.
+    pub fn undefined(&self) -> Vec<(&str, &str)> {
+        let mut undefined: Vec<(&str, &str)> = (0..self.len())
+            .filter(|id| !self.defined[*id])
+            .flat_map(|id| self.containers[id].iter().map(move |(outer, _)| (self.name(*outer), self.name(id))))
+            .collect();
+        undefined.sort_unstable();
+        undefined
+    }
+
+    /// Every bag that can eventually hold `id`, in id order which is name order for bags with
+    /// rules, not counting `id` itself.
+    pub fn ancestors(&self, id: BagId) -> Vec<BagId> {
+        if let Some(ancestors) = self.ancestors.borrow().get(&id) {
+            return ancestors.clone();
+        }
+
+        let ancestors = self.reachable(id, |bag| self.containers(bag));
+        self.ancestors.borrow_mut().insert(id, ancestors.clone());
+        ancestors
+    }
+
+    /// Every bag that `id` eventually holds, in id order, not counting `id` itself.
+    pub fn descendants(&self, id: BagId) -> Vec<BagId> {
+        self.reachable(id, |bag| self.contents(bag))
+    }
+
+    fn reachable<'a>(&'a self, id: BagId, edges: impl Fn(BagId) -> &'a [(BagId, u32)]) -> Vec<BagId> {
+        let mut seen = vec![false; self.len()];
+        let mut queue: VecDeque<BagId> = VecDeque::new();
+        queue.push_back(id);
+        while let Some(bag) = queue.pop_front() {
+            for (next, _) in edges(bag) {
+                if !seen[*next] {
+                    seen[*next] = true;
+                    queue.push_back(*next);
+                }
+            }
+        }
+        (0..self.len()).filter(|bag| seen[*bag] && *bag != id).collect()
+    }
+
+    /// How many bags `id` holds in total, all the way down, counted in `C`. Fails if `id`
+    /// can reach a cycle, as the bags would then be nested forever, or if the count does
+    /// not fit in `C`.
+    pub fn total_contents<C: Count>(&self, id: BagId) -> Result<C, GraphError> {
+        if let Some(total) = &C::cache(self).borrow()[id] {
+            return Ok(total.clone());
+        }
+        if let Some(cycle) = self.cycle_of[id] {
+            return Err(self.unbounded(id, cycle));
+        }
+
+        // The bags from `id` down to the one being counted, each with how many of its contents
+        // have been added to its total so far. A bag is only finished once everything it
+        // holds is in the cache.
+        let mut path: Vec<(BagId, usize, C)> = vec![(id, 0, C::from_u32(0))];
+        while let Some((bag, next, _)) = path.last() {
+            let (bag, next) = (*bag, *next);
+            let (inner, count) = match self.contents(bag).get(next) {
+                Some(edge) => *edge,
+                None => {
+                    let (_, _, total) = path.pop().unwrap();
+                    C::cache(self).borrow_mut()[bag] = Some(total.clone());
+                    if path.is_empty() {
+                        return Ok(total);
+                    }
+                    continue;
+                }
+            };
+
+            let inner_total = C::cache(self).borrow()[inner].clone();
+            let inner_total = match inner_total {
+                Some(inner_total) => inner_total,
+                None => {
+                    if let Some(cycle) = self.cycle_of[inner] {
+                        return Err(self.unbounded(inner, cycle));
+                    }
+                    path.push((inner, 0, C::from_u32(0)));
+                    continue;
+                }
+            };
+            let top = path.last_mut().unwrap();
+            let total = inner_total.checked_add(&C::from_u32(1))
+                .and_then(|bags| bags.checked_mul(&C::from_u32(count)))
+                .and_then(|bags| top.2.checked_add(&bags));
+            match total {
+                Some(total) => {
+                    top.2 = total;
+                    top.1 += 1;
+                },
+                None => return Err(GraphError::Overflow {
+                    path: path.iter().map(|(bag, _, _)| *bag).chain(Some(inner)).map(|id| self.name(id).to_string()).collect()
+                })
+            }
+        }
+        unreachable!("the path only empties by returning the total")
+    }
+
+    /// What `id` holds, level by level, depth first in rule order, with the running counts
+    /// in `C`. Levels past `max_depth` are left out. Fails like `total_contents` when the
+    /// nesting never ends or a count does not fit in `C`.
+    pub fn tree<C: Count>(&self, id: BagId, max_depth: Option<usize>) -> Result<Vec<TreeLevel<C>>, GraphError> {
+        let mut reached = std::iter::once(id).chain(self.descendants(id));
+        if let Some((bag, cycle)) = reached.find_map(|bag| self.cycle_of[bag].map(|cycle| (bag, cycle))) {
+            return Err(self.unbounded(bag, cycle));
+        }
+
+        // The bags from `id` down to the current one, with how many of each bag's contents
+        // have been listed and how many of the bag there are in all.
+        let mut levels = vec![];
+        let mut path: Vec<(BagId, usize, C)> = vec![(id, 0, C::from_u32(1))];
+        while !path.is_empty() {
+            let depth = path.len();
+            let (bag, next, in_all) = path.last_mut().unwrap();
+            let edge = if max_depth.is_some_and(|max| depth > max) {
+                None
+            } else {
+                self.contents(*bag).get(*next)
+            };
+            let (inner, count) = match edge {
+                Some(edge) => *edge,
+                None => {
+                    path.pop();
+                    continue;
+                }
+            };
+            *next += 1;
+            let inner_in_all = in_all.checked_mul(&C::from_u32(count)).ok_or_else(|| GraphError::Overflow {
+                path: path.iter().map(|(bag, _, _)| *bag).chain(Some(inner)).map(|id| self.name(id).to_string()).collect()
+            })?;
+            levels.push(TreeLevel { depth, bag: inner, count, in_all: inner_in_all.clone() });
+            path.push((inner, 0, inner_in_all));
+        }
+        Ok(levels)
+    }
+
+    /// The error for `bag`, which is on `cycle` in `cycles`.
+    fn unbounded(&self, bag: BagId, cycle: usize) -> GraphError {
+        GraphError::Unbounded {
+            bag: self.name(bag).to_string(),
+            cycle: self.cycles[cycle].iter().map(|id| self.name(*id).to_string()).collect()
+        }
+    }
+}
+
+/// One line of a containment tree.
+#[derive(Debug, PartialEq)]
+pub struct TreeLevel<C> {
+    /// 1 for the bags held directly.
+    pub depth: usize,
+    pub bag: BagId,
+    /// How many the bag one level up holds.
+    pub count: u32,
+    /// How many there are at this point of the tree, all the copies of the bags above
+    /// multiplied out.
+    pub in_all: C
+}
+
+/// A number type bags can be counted in. Each has its own cache in the graph.
+pub trait Count: Clone + Sized {
+    fn from_u32(n: u32) -> Self;
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+    fn checked_mul(&self, other: &Self) -> Option<Self>;
+    fn cache(graph: &BagGraph) -> &RefCell<Vec<Option<Self>>>;
+}
+
+impl Count for u64 {
+    fn from_u32(n: u32) -> Self {
+        n as u64
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        u64::checked_add(*self, *other)
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self> {
+        u64::checked_mul(*self, *other)
+    }
+
+    fn cache(graph: &BagGraph) -> &RefCell<Vec<Option<Self>>> {
+        &graph.totals
+    }
+}
+
+/// Never overflows.
+impl Count for BigUint {
+    fn from_u32(n: u32) -> Self {
+        BigUint::from(n)
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        Some(self + other)
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self> {
+        Some(self * other)
+    }
+
+    fn cache(graph: &BagGraph) -> &RefCell<Vec<Option<Self>>> {
+        &graph.big_totals
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum GraphError {
+    /// Counting the contents of `bag` runs into bags that hold each other.
+    Unbounded { bag: String, cycle: Vec<String> },
+    /// The count went past the largest number the count type holds, on the way from the
+    /// first bag of `path` to the last.
+    Overflow { path: Vec<String> }
+}
+
+impl std::fmt::Display for GraphError {
+    fn fmt (&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GraphError::Unbounded { bag, cycle } => {
+                write!(f, "{} is nested forever: {}", bag, cycle.join(" -> "))
+            },
+            GraphError::Overflow { path } => {
+                write!(f, "too many bags to count along {}", path.join(" -> "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for GraphError {}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::to_hashmap;
+
+    const EXAMPLE: &str = "light red bags contain 1 bright white bag, 2 muted yellow bags.
+dark orange bags contain 3 bright white bags, 4 muted yellow bags.
+bright white bags contain 1 shiny gold bag.
+muted yellow bags contain 2 shiny gold bags, 9 faded blue bags.
+shiny gold bags contain 1 dark olive bag, 2 vibrant plum bags.
+dark olive bags contain 3 faded blue bags, 4 dotted black bags.
+vibrant plum bags contain 5 faded blue bags, 6 dotted black bags.
+faded blue bags contain no other bags.
+dotted black bags contain no other bags.";
+
+    fn graph(text: &str) -> BagGraph {
+        BagGraph::new(&to_hashmap(text).unwrap())
+    }
+
+    fn names(graph: &BagGraph, ids: Vec<BagId>) -> Vec<&str> {
+        ids.into_iter().map(|id| graph.name(id)).collect()
+    }
+
+    #[test]
+    fn example() {
+        let graph = graph(EXAMPLE);
+        let gold = graph.id("shiny gold bag").unwrap();
+        assert_eq!(names(&graph, graph.ancestors(gold)),
+            ["bright white bag", "dark orange bag", "light red bag", "muted yellow bag"]);
+        assert_eq!(graph.total_contents(gold), Ok(32u64));
+        // Answers come from the cache the second time.
+        assert_eq!(graph.ancestors(gold).len(), 4);
+        assert_eq!(graph.total_contents(gold), Ok(32u64));
+        assert!(graph.undefined().is_empty());
+    }
+
+    #[test]
+    fn tree() {
+        let graph = graph(EXAMPLE);
+        let gold = graph.id("shiny gold bag").unwrap();
+        let lines: Vec<String> = graph.tree::<u64>(gold, None).unwrap().iter()
+            .map(|level| format!("{} {} {} {}", level.depth, level.count, graph.name(level.bag), level.in_all))
+            .collect();
+        assert_eq!(lines, [
+            "1 1 dark olive bag 1",
+            "2 3 faded blue bag 3",
+            "2 4 dotted black bag 4",
+            "1 2 vibrant plum bag 2",
+            "2 5 faded blue bag 10",
+            "2 6 dotted black bag 12",
+        ]);
+        assert_eq!(graph.tree::<u64>(gold, Some(1)).unwrap().len(), 2);
+        assert!(graph.tree::<u64>(gold, Some(0)).unwrap().is_empty());
+        let in_all: u64 = graph.tree::<u64>(gold, None).unwrap().iter().map(|level| level.in_all).sum();
+        assert_eq!(Ok(in_all), graph.total_contents(gold));
+    }
+
+    #[test]
+    fn adjacency() {
+        let graph = graph(EXAMPLE);
+        let white = graph.id("bright white bag").unwrap();
+        let containers: Vec<(&str, u32)> = graph.containers(white).iter().map(|(id, n)| (graph.name(*id), *n)).collect();
+        assert_eq!(containers, [("dark orange bag", 3), ("light red bag", 1)]);
+        assert_eq!(graph.contents(white), [(graph.id("shiny gold bag").unwrap(), 1)]);
+    }
+
+    #[test]
+    fn undefined() {
+        let graph = graph("light red bags contain 1 bright white bag, 2 muted yellow bags.\nbright white bags contain no other bags.");
+        assert_eq!(graph.undefined(), vec![("light red bag", "muted yellow bag")]);
+        assert_eq!(graph.total_contents(graph.id("light red bag").unwrap()), Ok(3u64));
+    }
+
+    #[test]
+    fn cycles() {
+        let graph = graph("light red bags contain 1 bright white bag.
+bright white bags contain 2 muted yellow bags, 1 faded blue bag.
+muted yellow bags contain 1 light red bag.
+faded blue bags contain no other bags.
+dark orange bags contain 3 bright white bags.
+dotted black bags contain 1 dotted black bag.");
+        assert_eq!(graph.cycles(), vec![
+            vec!["bright white bag", "muted yellow bag", "light red bag", "bright white bag"],
+            vec!["dotted black bag", "dotted black bag"],
+        ]);
+        let error = graph.total_contents::<u64>(graph.id("dark orange bag").unwrap()).unwrap_err();
+        assert_eq!(error.to_string(),
+            "bright white bag is nested forever: bright white bag -> muted yellow bag -> light red bag -> bright white bag");
+        assert_eq!(graph.total_contents(graph.id("faded blue bag").unwrap()), Ok(0u64));
+        // Finding the containers of a bag on a cycle is still fine.
+        assert_eq!(names(&graph, graph.ancestors(graph.id("light red bag").unwrap())),
+            ["bright white bag", "dark orange bag", "muted yellow bag"]);
+        assert!(graph.total_contents::<BigUint>(graph.id("dotted black bag").unwrap()).is_err());
+        assert_eq!(graph.tree::<u64>(graph.id("dark orange bag").unwrap(), Some(1)).unwrap_err(), error);
+    }
+
+    #[test]
+    fn overflow() {
+        // Each bag holds a million of the next, so the shiny gold bag holds just over 10^36.
+        let colours = ["shiny gold", "dark red", "light blue", "muted green", "faded pink", "dim teal", "pale plum"];
+        let mut rules: Vec<String> = colours.windows(2)
+            .map(|pair| format!("{} bags contain 1000000 {} bags.", pair[0], pair[1]))
+            .collect();
+        rules.push("pale plum bags contain no other bags.".to_string());
+        let graph = graph(&rules.join("\n"));
+        let gold = graph.id("shiny gold bag").unwrap();
+
+        let error = graph.total_contents::<u64>(gold).unwrap_err();
+        assert_eq!(error.to_string(),
+            "too many bags to count along shiny gold bag -> dark red bag -> light blue bag -> muted green bag");
+        let expected: BigUint = (1..7).map(|level| BigUint::from(1_000_000u32).pow(level)).sum();
+        assert_eq!(graph.total_contents::<BigUint>(gold), Ok(expected));
+        // Bags further down still fit.
+        assert_eq!(graph.total_contents(graph.id("muted green bag").unwrap()), Ok(1_000_001_000_001_000_000u64));
+
+        assert_eq!(graph.tree::<u64>(gold, None).unwrap_err().to_string(),
+            "too many bags to count along shiny gold bag -> dark red bag -> light blue bag -> muted green bag -> faded pink bag");
+        let tree = graph.tree::<BigUint>(gold, None).unwrap();
+        assert_eq!(tree.last().unwrap().in_all, BigUint::from(1_000_000u32).pow(6));
+    }
+
+    #[test]
+    fn deep_nesting() {
+        // Far deeper than the stack would allow one call per level.
+        let depth = 100_000;
+        let mut rules: Vec<String> = (0..depth).map(|n| format!("c{} x bags contain 1 c{} x bag.", n, n + 1)).collect();
+        rules.push(format!("c{} x bags contain no other bags.", depth));
+        let graph = graph(&rules.join("\n"));
+        assert!(graph.cycles().is_empty());
+        let top = graph.id("c0 x bag").unwrap();
+        assert_eq!(graph.total_contents(top), Ok(depth as u64));
+        let tree = graph.tree::<u64>(top, None).unwrap();
+        assert_eq!(tree.len(), depth);
+        assert_eq!(tree.last().unwrap().depth, depth);
+        assert_eq!(graph.ancestors(graph.id(&format!("c{} x bag", depth)).unwrap()).len(), depth);
+    }
+}
@@ -1,9 +1,15 @@
-use std::fs::File;
-use std::io::{self, BufRead, Error};
+use std::convert::TryFrom;
 use std::path::Path;
 use std::collections::HashMap;
 use std::fs;
 
+mod export;
+mod graph;
+
+use export::{export, Format, Scope};
+use graph::{BagGraph, TreeLevel};
+use num_bigint::BigUint;
+
 struct BagRule {
     bag_type: String,
     count: u32
@@ -13,91 +19,282 @@ impl BagRule {
     fn new( bag_type: String, count: u32 ) -> BagRule {
         BagRule { bag_type, count }
     }
+}
+
+/// What is wrong with a line of the rules.
+#[derive(Debug, PartialEq)]
+enum ParseProblem {
+    /// There is no ` contain ` between the bag and its contents.
+    MissingContain,
+    /// A content has no space between the count and the bag.
+    MissingBag(String),
+    BadCount(String)
+}
 
-    fn contains_recur(&self, bag: &str, collection: &HashMap<String, Vec<BagRule>>) -> bool {
-        if self.bag_type == bag {
-            return true;
+#[derive(Debug, PartialEq)]
+struct ParseError {
+    /// Counted from 1.
+    line: usize,
+    text: String,
+    problem: ParseProblem
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt (&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.problem {
+            ParseProblem::MissingContain => write!(f, "expected 'contain'")?,
+            ParseProblem::MissingBag(content) => write!(f, "'{}' is not a count and a bag", content)?,
+            ParseProblem::BadCount(count) => write!(f, "'{}' is not a count", count)?
         }
-        collection
-            .get(&self.bag_type)
-            .unwrap()
-            .iter()
-            .any(|br| br.contains_recur(bag, collection))
+        write!(f, " in '{}'", self.text)
     }
+}
 
-    fn bag_count(&self, collection: &HashMap<String, Vec<BagRule>>, prev_count: u32) -> u32 {
-        let rules = collection.get(&self.bag_type).unwrap();
-        if rules.is_empty() {
-            prev_count
-        } else {
-            rules
-                .iter()
-                .map(|br| br.bag_count(collection, br.count * prev_count))
-                .sum::<u32>()
-                + prev_count
-        }
+impl std::error::Error for ParseError {}
+
+impl TryFrom<&str> for BagRule {
+    type Error = ParseProblem;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        let s = s.trim();
+        let (count, bag) = s.split_once(char::is_whitespace).ok_or_else(|| ParseProblem::MissingBag(s.to_string()))?;
+        let num: u32 = count.parse().map_err(|_| ParseProblem::BadCount(count.to_string()))?;
+        Ok(BagRule::new(normalize_colour(bag), num))
     }
 }
 
-impl From<&str> for BagRule {
-    fn from(s: &str)-> Self {
-        match s.find(" ") {
-            Some(n) => {
-                let num: u32 = s[0..n].parse().unwrap();
-                BagRule::new(
-                    String::from(s[n + 1..].trim_end_matches("s")),
-                    num,
-                )
-            },
-            None => panic!("Dammit")
-        }
+/// The name a bag colour is stored under: lower case, single spaces and ending in a single
+/// "bag", so "Shiny  Gold bags", "shiny gold" and "shiny gold bag" are all "shiny gold bag".
+fn normalize_colour(name: &str) -> String {
+    let lower = name.to_lowercase();
+    let mut words: Vec<&str> = lower.split_whitespace().collect();
+    if let Some(last) = words.last_mut() {
+        *last = last.trim_end_matches('.');
+    }
+    if matches!(words.last(), Some(&"bag") | Some(&"bags")) {
+        words.pop();
     }
+    words.push("bag");
+    words.join(" ")
 }
 
-fn to_hashmap(input: &str) -> HashMap<String, Vec<BagRule>> {
+/// Blank lines are skipped.
+fn to_hashmap(input: &str) -> Result<HashMap<String, Vec<BagRule>>, ParseError> {
     input.lines()
-        .map(|line| {
-            let mut split = line.split(" contain ");
-            let bag = split.next().unwrap().trim_end_matches("s");
-            let unparsed_rules = split.next().unwrap().trim_end_matches(".");
-            let rules: Vec<BagRule> = if unparsed_rules == "no other bags" {
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            let error = |problem| ParseError { line: i + 1, text: line.to_string(), problem };
+            let mut split = line.trim().splitn(2, " contain ");
+            let bag = normalize_colour(split.next().unwrap_or(""));
+            let unparsed_rules = split.next().ok_or_else(|| error(ParseProblem::MissingContain))?.trim().trim_end_matches('.');
+            let no_others = unparsed_rules.to_lowercase().split_whitespace().eq(["no", "other", "bags"].iter().copied());
+            let rules: Vec<BagRule> = if no_others {
                 vec![]
             } else {
-                unparsed_rules.split(", ").map(|s| s.into()).collect()
+                unparsed_rules.split(',')
+                    .map(BagRule::try_from)
+                    .collect::<Result<_, _>>()
+                    .map_err(error)?
             };
-            (String::from(bag), rules)
+            Ok((bag, rules))
         } )
-        .collect() 
+        .collect()
 }
 
-fn main() -> Result<(), Error>{
-    let filename = Path::new("input.txt");
-    let contents = fs::read_to_string(filename)
-    .expect("Something went wrong reading the file");
-
-    let hash_map = to_hashmap(&contents);
-    
-    let count = hash_map.iter()
-        .filter(|(bag, rules)| {
-            if bag.as_str() == "shiny gold bag" {
-                false
-            } else {
-                rules
-                    .iter()
-                    .any(|br| br.contains_recur("shiny gold bag", &hash_map))
+/// Usage: `day7 [COLOUR] [--list] [--tree] [--depth N] [--big]`, where the colour defaults
+/// to shiny gold. `--list` names the bags that can hold it, `--tree` shows what it holds
+/// level by level, down to `--depth` levels. `--export dot|mermaid` with `--from COLOUR` or
+/// `--to COLOUR` writes the graph instead.
+struct Options {
+    /// Every word that is not an option or its value is part of the colour.
+    colour: Vec<String>,
+    list: bool,
+    tree: bool,
+    depth: Option<usize>,
+    big: bool,
+    export: Option<Format>,
+    from: Option<String>,
+    to: Option<String>
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Options, String> {
+        let mut options = Options { colour: vec![], list: false, tree: false, depth: None, big: false, export: None, from: None, to: None };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+            match arg.as_str() {
+                "--list" => options.list = true,
+                "--tree" => options.tree = true,
+                "--depth" => {
+                    let depth = value()?;
+                    options.depth = Some(depth.parse()
+                        .map_err(|_| format!("Bad depth '{}' for --depth, expected a number of levels", depth))?);
+                },
+                "--big" => options.big = true,
+                "--export" => options.export = Some(value()?.parse()?),
+                "--from" => options.from = Some(value()?.clone()),
+                "--to" => options.to = Some(value()?.clone()),
+                _ if arg.starts_with("--") => return Err(format!("Unknown option {}", arg)),
+                _ => options.colour.push(arg.clone())
             }
-        })
-        .count();
-    
-    println! ("{}", count);
+        }
 
-    let rules = hash_map.get("shiny gold bag").unwrap();
-    let count2 =  rules
-        .iter()
-        .map(|br| br.bag_count(&hash_map, br.count))
-        .sum::<u32>();
+        if options.from.is_some() && options.to.is_some() {
+            return Err("--from and --to cannot be used together".to_string());
+        }
+        if options.export.is_none() && (options.from.is_some() || options.to.is_some()) {
+            return Err("--from and --to only apply to --export".to_string());
+        }
+        if options.export.is_some() && (options.list || options.tree || !options.colour.is_empty()) {
+            return Err("--export writes the graph, use --from or --to to pick a bag".to_string());
+        }
+        if options.depth.is_some() && !options.tree {
+            return Err("--depth only applies to --tree".to_string());
+        }
+        Ok(options)
+    }
+}
 
-    println! ("contains {}", count2);
+/// Errors are printed with their messages and the exit code is 1.
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = Options::parse(&args)?;
+
+    let filename = Path::new("input.txt");
+    let contents = fs::read_to_string(filename)?;
+
+    let graph = BagGraph::new(&to_hashmap(&contents)?);
+
+    let bag = |name: &str| {
+        let colour = normalize_colour(name);
+        graph.id(&colour).ok_or_else(|| format!("There is no rule for {}", colour))
+    };
+
+    if let Some(format) = options.export {
+        let scope = match (&options.from, &options.to) {
+            (Some(name), _) => Scope::Contents(bag(name)?),
+            (None, Some(name)) => Scope::Containers(bag(name)?),
+            (None, None) => Scope::All
+        };
+        export(&graph, scope, format, std::io::stdout().lock())?;
+        return Ok(());
+    }
+
+    for (container, bag) in graph.undefined() {
+        println!("Warning: {} contains {}, which has no rule", container, bag);
+    }
+    for cycle in graph.cycles() {
+        println!("Warning: bags hold each other: {}", cycle.join(" -> "));
+    }
+
+    let colour = if options.colour.is_empty() { "shiny gold".to_string() } else { options.colour.join(" ") };
+    let target = bag(&colour)?;
+    let name = graph.name(target);
+
+    let containers = graph.ancestors(target);
+    println! ("{} bags can hold a {}", containers.len(), name);
+    if options.list {
+        for id in containers {
+            println!("  {}", graph.name(id));
+        }
+    }
+
+    if options.big {
+        println! ("A {} holds {} bags", name, graph.total_contents::<BigUint>(target)?);
+    } else {
+        println! ("A {} holds {} bags", name, graph.total_contents::<u64>(target)?);
+    }
+
+    if options.tree {
+        println!("{}", name);
+        if options.big {
+            print_tree(&graph, graph.tree::<BigUint>(target, options.depth)?);
+        } else {
+            print_tree(&graph, graph.tree::<u64>(target, options.depth)?);
+        }
+    }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+fn print_tree<C: std::fmt::Display>(graph: &BagGraph, levels: Vec<TreeLevel<C>>) {
+    for level in levels {
+        println!("{}{} {} ({} in all)", "  ".repeat(level.depth), level.count, graph.name(level.bag), level.in_all);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn options(args: &str) -> Result<Options, String> {
+        Options::parse(&args.split_whitespace().map(String::from).collect::<Vec<String>>())
+    }
+
+    #[test]
+    fn command_line() {
+        let parsed = options("dark  red --tree --depth 2 --big").unwrap();
+        assert_eq!(parsed.colour, ["dark", "red"]);
+        assert!(parsed.tree && parsed.big && !parsed.list);
+        assert_eq!(parsed.depth, Some(2));
+        assert_eq!(options("--export dot --to gold").unwrap().to.as_deref(), Some("gold"));
+
+        assert_eq!(options("--tre").err().unwrap(), "Unknown option --tre");
+        assert_eq!(options("--tree --depth x").err().unwrap(), "Bad depth 'x' for --depth, expected a number of levels");
+        assert!(options("--export dot --from red --to blue").is_err());
+        assert!(options("--from red").is_err());
+        assert!(options("--export dot red").is_err());
+        assert!(options("--depth 2").is_err());
+        assert!(options("--export svg").is_err());
+        assert!(options("--export").is_err());
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = to_hashmap("light red bags contain 1 bright white bag.\n\nfaded blue bags hold nothing.").err().unwrap();
+        assert_eq!(error.line, 3);
+        assert_eq!(error.problem, ParseProblem::MissingContain);
+        assert_eq!(error.to_string(), "line 3: expected 'contain' in 'faded blue bags hold nothing.'");
+
+        let error = to_hashmap("light red bags contain two bright white bags.").err().unwrap();
+        assert_eq!(error.problem, ParseProblem::BadCount("two".to_string()));
+        let error = to_hashmap("light red bags contain bags.").err().unwrap();
+        assert_eq!(error.problem, ParseProblem::MissingBag("bags".to_string()));
+    }
+
+    #[test]
+    fn colour_names() {
+        for name in ["shiny gold", "shiny gold bag", "shiny gold bags", " Shiny  Gold bags.", "shiny\tgold bag"].iter() {
+            assert_eq!(normalize_colour(name), "shiny gold bag");
+        }
+        // Only a final "bag" or "bags" is dropped, not every trailing s.
+        assert_eq!(normalize_colour("dull brass bags"), "dull brass bag");
+        assert_eq!(normalize_colour("dull brass"), "dull brass bag");
+
+        let rules = to_hashmap("Light Red bags contain 1 bright white bag,2  muted yellow bags .\nmuted yellow bags contain no other bags.").unwrap();
+        let contents: Vec<(&str, u32)> = rules["light red bag"].iter().map(|br| (br.bag_type.as_str(), br.count)).collect();
+        assert_eq!(contents, [("bright white bag", 1), ("muted yellow bag", 2)]);
+        assert!(rules["muted yellow bag"].is_empty());
+
+        let rules = to_hashmap("Faded Blue bags contain No  Other bags.").unwrap();
+        assert!(rules["faded blue bag"].is_empty());
+    }
+
+    #[test]
+    fn multibyte_whitespace() {
+        let rules = to_hashmap("light red bags contain 1\u{a0}bright white bag.").unwrap();
+        assert_eq!(rules["light red bag"][0].bag_type, "bright white bag");
+        assert_eq!(rules["light red bag"][0].count, 1);
+    }
+}
@@ -0,0 +1,106 @@
+//! Reads input made of records separated by blank lines, as used by the passport (day4)
+//! and customs answer (day6) puzzles.
+
+use std::io::{self, BufRead, BufReader, Read};
+
+/// The lines of one record, and the line number (starting at 1) where it begins.
+#[derive(Debug, PartialEq)]
+pub struct Record {
+    pub line: usize,
+    pub lines: Vec<String>
+}
+
+impl Record {
+    /// All the lines joined by a single space.
+    pub fn text(&self) -> String {
+        self.lines.join(" ")
+    }
+}
+
+/// Iterator over the blank line separated records of any reader. Lines that only hold
+/// whitespace count as blank, runs of blank lines are skipped, and the last record is
+/// returned whether or not the input ends with a blank line.
+pub struct Records<R: Read> {
+    lines: io::Lines<BufReader<R>>,
+    line_number: usize
+}
+
+impl<R: Read> Records<R> {
+    pub fn new(reader: R) -> Records<R> {
+        Records { lines: BufReader::new(reader).lines(), line_number: 0 }
+    }
+}
+
+impl<R: Read> Iterator for Records<R> {
+    type Item = io::Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut record = Record { line: 0, lines: vec![] };
+        for line in self.lines.by_ref() {
+            self.line_number += 1;
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e))
+            };
+
+            if line.trim().is_empty() {
+                if !record.lines.is_empty() {
+                    return Some(Ok(record));
+                }
+            } else {
+                if record.lines.is_empty() {
+                    record.line = self.line_number;
+                }
+                record.lines.push(line);
+            }
+        }
+
+        if record.lines.is_empty() {
+            None
+        } else {
+            Some(Ok(record))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn read(input: &str) -> Vec<Record> {
+        Records::new(input.as_bytes()).map(|r| r.unwrap()).collect()
+    }
+
+    #[test]
+    fn last_record_without_blank_line() {
+        let records = read("a b\nc\n\nd\ne");
+        assert_eq!(records, vec![
+            Record { line: 1, lines: vec!["a b".to_string(), "c".to_string()] },
+            Record { line: 4, lines: vec!["d".to_string(), "e".to_string()] },
+        ]);
+        assert_eq!(records[0].text(), "a b c");
+    }
+
+    #[test]
+    fn whitespace_lines_are_blank() {
+        let records = read("\n \n a\n\t\n\n  \nb\n\n");
+        assert_eq!(records, vec![
+            Record { line: 3, lines: vec![" a".to_string()] },
+            Record { line: 7, lines: vec!["b".to_string()] },
+        ]);
+    }
+
+    #[test]
+    fn empty_input() {
+        assert!(read("").is_empty());
+        assert!(read("\n  \n").is_empty());
+    }
+
+    #[test]
+    fn crlf_lines() {
+        let records = read("a\r\n\r\nb\r\n");
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].lines, ["b"]);
+    }
+}